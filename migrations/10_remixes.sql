@@ -0,0 +1,6 @@
+CREATE TABLE remixes (
+	activity_id uuid PRIMARY KEY REFERENCES activities (id) ON DELETE CASCADE,
+	derived_from text NOT NULL
+);
+
+CREATE INDEX remixes_derived_from_idx ON remixes (derived_from);
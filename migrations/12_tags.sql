@@ -0,0 +1,7 @@
+CREATE TABLE tags (
+	activity_id uuid NOT NULL REFERENCES activities (id) ON DELETE CASCADE,
+	name text NOT NULL,
+	PRIMARY KEY (name, activity_id)
+);
+
+CREATE INDEX tags_activity_id_idx ON tags (activity_id);
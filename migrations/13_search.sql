@@ -0,0 +1,11 @@
+ALTER TABLE activities ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+	to_tsvector('simple', COALESCE(activity->'object'->>'name', '') || ' ' || COALESCE(activity->'object'->>'summary', ''))
+) STORED;
+
+CREATE INDEX activities_search_vector_idx ON activities USING GIN (search_vector);
+
+ALTER TABLE users ADD COLUMN search_vector tsvector GENERATED ALWAYS AS (
+	to_tsvector('simple', username || ' ' || COALESCE(name, '') || ' ' || COALESCE(bio, ''))
+) STORED;
+
+CREATE INDEX users_search_vector_idx ON users USING GIN (search_vector);
@@ -0,0 +1 @@
+CREATE INDEX activities_public_published_at_idx ON activities (published_at) WHERE is_public = TRUE;
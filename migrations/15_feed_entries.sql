@@ -0,0 +1,23 @@
+CREATE TABLE feed_entries (
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	activity_id uuid NOT NULL REFERENCES activities (id) ON DELETE CASCADE,
+	published_at timestamp WITHOUT TIME ZONE NOT NULL,
+	PRIMARY KEY (user_id, activity_id)
+);
+
+CREATE INDEX feed_entries_user_id_published_at_idx ON feed_entries (user_id, published_at);
+CREATE INDEX feed_entries_activity_id_idx ON feed_entries (activity_id);
+
+CREATE INDEX follows_object_user_id_idx ON follows (object_user_id);
+CREATE INDEX activities_to_followers_of_idx ON activities USING GIN (to_followers_of);
+CREATE INDEX activities_cc_followers_of_idx ON activities USING GIN (cc_followers_of);
+
+-- Feeds are filled with activities that existed before this migration by
+-- a background job, newest first. The cursor is the last processed one.
+CREATE TABLE feed_backfill (
+	cursor_published_at timestamp WITHOUT TIME ZONE NOT NULL,
+	cursor_activity_id uuid NOT NULL,
+	is_done boolean NOT NULL DEFAULT FALSE
+);
+
+INSERT INTO feed_backfill (cursor_published_at, cursor_activity_id) VALUES ('9999-12-31 23:59:59', 'ffffffff-ffff-ffff-ffff-ffffffffffff');
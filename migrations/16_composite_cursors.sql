@@ -0,0 +1,9 @@
+-- Collection pages are ordered by (published_at, id), so the id is needed in
+-- the indexes as well to keep page queries from sorting.
+DROP INDEX activities_public_published_at_idx;
+CREATE INDEX activities_public_published_at_id_idx ON activities (published_at, id) WHERE is_public = TRUE;
+
+DROP INDEX feed_entries_user_id_published_at_idx;
+CREATE INDEX feed_entries_user_id_published_at_activity_id_idx ON feed_entries (user_id, published_at, activity_id);
+
+CREATE INDEX activities_user_id_published_at_id_idx ON activities (user_id, published_at, id);
@@ -0,0 +1,3 @@
+ALTER TABLE activities ADD COLUMN is_local_only boolean NOT NULL DEFAULT FALSE;
+
+CREATE INDEX activities_local_only_published_at_id_idx ON activities (published_at, id) WHERE is_local_only = TRUE;
@@ -0,0 +1,20 @@
+CREATE TABLE audience_lists (
+	id uuid PRIMARY KEY,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	name text NOT NULL,
+	created_at timestamp WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'utc') NOT NULL
+);
+
+CREATE INDEX audience_lists_user_id_idx ON audience_lists (user_id);
+
+CREATE TABLE audience_list_members (
+	list_id uuid NOT NULL REFERENCES audience_lists (id) ON DELETE CASCADE,
+	member_user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	PRIMARY KEY (list_id, member_user_id)
+);
+
+CREATE INDEX audience_list_members_member_user_id_idx ON audience_list_members (member_user_id);
+
+ALTER TABLE activities ADD COLUMN audience_lists uuid[] NOT NULL DEFAULT '{}';
+
+CREATE INDEX activities_audience_lists_idx ON activities USING GIN (audience_lists);
@@ -0,0 +1,14 @@
+CREATE TABLE lists (
+	id uuid PRIMARY KEY,
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	name text NOT NULL,
+	created_at timestamp WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'utc') NOT NULL
+);
+
+CREATE INDEX lists_user_id_idx ON lists (user_id);
+
+CREATE TABLE list_members (
+	list_id uuid NOT NULL REFERENCES lists (id) ON DELETE CASCADE,
+	member_user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	PRIMARY KEY (list_id, member_user_id)
+);
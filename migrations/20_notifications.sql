@@ -0,0 +1,9 @@
+CREATE TABLE notifications (
+	user_id uuid NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+	kind text NOT NULL,
+	activity_id uuid NOT NULL REFERENCES activities (id) ON DELETE CASCADE,
+	created_at timestamp WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'utc') NOT NULL,
+	PRIMARY KEY (user_id, kind, activity_id)
+);
+
+CREATE INDEX notifications_user_id_created_at_idx ON notifications (user_id, created_at);
@@ -0,0 +1 @@
+ALTER TABLE users ADD COLUMN public_key_fetched_at timestamp WITHOUT TIME ZONE;
@@ -0,0 +1,14 @@
+ALTER TABLE media DROP COLUMN reference_count;
+
+CREATE TABLE activity_media (
+	activity_id uuid REFERENCES activities (id) ON DELETE CASCADE NOT NULL,
+	media_id uuid REFERENCES media (id) NOT NULL,
+	PRIMARY KEY (activity_id, media_id)
+);
+
+CREATE INDEX activity_media_media_id_idx ON activity_media (media_id);
+
+-- URLs of stored files and their variants start with the ID of the file.
+INSERT INTO activity_media (activity_id, media_id)
+	SELECT activities.id, media.id FROM activities JOIN media ON activities.activity::text LIKE '%' || media.id::text || '%'
+	WHERE activities.this_instance = TRUE;
@@ -0,0 +1,5 @@
+CREATE TABLE web_finger_cache (
+	handle text PRIMARY KEY,
+	actor_url text NOT NULL,
+	resolved_at timestamp WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'utc') NOT NULL
+);
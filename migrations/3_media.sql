@@ -0,0 +1,11 @@
+CREATE TABLE media (
+	id uuid PRIMARY KEY,
+	user_id uuid REFERENCES users (id) NOT NULL,
+	media_type text NOT NULL,
+	size bigint NOT NULL,
+	width integer,
+	height integer,
+	uploaded_at timestamp WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'utc') NOT NULL
+);
+
+CREATE INDEX media_user_id_idx ON media (user_id);
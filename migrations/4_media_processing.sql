@@ -0,0 +1,11 @@
+ALTER TABLE media ADD COLUMN blurhash text;
+
+CREATE TABLE media_variants (
+	media_id uuid REFERENCES media (id) ON DELETE CASCADE NOT NULL,
+	name text NOT NULL,
+	media_type text NOT NULL,
+	size bigint NOT NULL,
+	width integer NOT NULL,
+	height integer NOT NULL,
+	PRIMARY KEY (media_id, name)
+);
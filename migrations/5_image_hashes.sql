@@ -0,0 +1,9 @@
+ALTER TABLE media ADD COLUMN phash bigint;
+
+CREATE TABLE image_hashes (
+	activity_id uuid PRIMARY KEY REFERENCES activities (id) ON DELETE CASCADE,
+	phash bigint NOT NULL,
+	phash_bands integer[] NOT NULL
+);
+
+CREATE INDEX image_hashes_phash_bands_idx ON image_hashes USING GIN (phash_bands);
@@ -0,0 +1,11 @@
+CREATE TABLE remote_media (
+	id uuid PRIMARY KEY,
+	url text UNIQUE NOT NULL,
+	media_type text,
+	size bigint,
+	phash bigint,
+	cached_at timestamp WITHOUT TIME ZONE,
+	last_accessed_at timestamp WITHOUT TIME ZONE
+);
+
+CREATE INDEX remote_media_last_accessed_at_idx ON remote_media (last_accessed_at);
@@ -0,0 +1,15 @@
+ALTER TABLE media ADD COLUMN sha256 bytea UNIQUE;
+ALTER TABLE media ADD COLUMN reference_count integer DEFAULT 1 NOT NULL;
+
+CREATE TABLE media_references (
+	user_id uuid REFERENCES users (id) NOT NULL,
+	media_id uuid REFERENCES media (id) ON DELETE CASCADE NOT NULL,
+	size bigint NOT NULL,
+	uploaded_at timestamp WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'utc') NOT NULL,
+	PRIMARY KEY (user_id, media_id)
+);
+
+CREATE INDEX media_references_media_id_idx ON media_references (media_id);
+
+INSERT INTO media_references (user_id, media_id, size, uploaded_at)
+	SELECT user_id, id, size + COALESCE((SELECT SUM(size) FROM media_variants WHERE media_id = media.id), 0), uploaded_at FROM media;
@@ -0,0 +1 @@
+ALTER TABLE media_references ADD COLUMN alt_text text;
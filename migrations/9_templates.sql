@@ -0,0 +1,10 @@
+CREATE TABLE templates (
+	id uuid PRIMARY KEY,
+	user_id uuid REFERENCES users (id) NOT NULL,
+	name text NOT NULL,
+	media_id uuid REFERENCES media (id) NOT NULL,
+	text_boxes jsonb NOT NULL,
+	created_at timestamp WITHOUT TIME ZONE DEFAULT (NOW() AT TIME ZONE 'utc') NOT NULL
+);
+
+CREATE INDEX templates_name_idx ON templates (name);
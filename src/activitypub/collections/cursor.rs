@@ -0,0 +1,116 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use chrono::NaiveDateTime;
+use uuid::Uuid;
+
+/// When the item was added, and an ID telling apart items added at the same
+/// time.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Cursor {
+	pub timestamp: NaiveDateTime,
+	pub id: Uuid,
+}
+
+impl Cursor {
+	pub fn new(timestamp: NaiveDateTime, id: Uuid) -> Self {
+		Self { timestamp, id }
+	}
+
+	pub fn min() -> Self {
+		Self {
+			timestamp: NaiveDateTime::from_timestamp(0, 0),
+			id: Uuid::nil(),
+		}
+	}
+
+	pub fn encode(&self) -> String {
+		// PostgreSQL timestamps have microsecond precision.
+		let micros = self.timestamp.timestamp() * 1_000_000
+			+ i64::from(self.timestamp.timestamp_subsec_micros());
+
+		let mut bytes = Vec::with_capacity(24);
+		bytes.extend_from_slice(&micros.to_be_bytes());
+		bytes.extend_from_slice(self.id.as_bytes());
+
+		base64::encode_config(bytes, base64::URL_SAFE_NO_PAD)
+	}
+
+	pub fn decode(encoded: &str) -> Option<Self> {
+		let bytes = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD).ok()?;
+		if bytes.len() != 24 {
+			return None;
+		}
+
+		let micros = i64::from_be_bytes(bytes[..8].try_into().ok()?);
+		let timestamp = NaiveDateTime::from_timestamp_opt(
+			micros.div_euclid(1_000_000),
+			u32::try_from(micros.rem_euclid(1_000_000) * 1000).ok()?,
+		)?;
+		let id = Uuid::from_slice(&bytes[8..]).ok()?;
+
+		Some(Self { timestamp, id })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use chrono::NaiveDate;
+
+	#[test]
+	fn round_trips() {
+		let cursor = Cursor::new(
+			NaiveDate::from_ymd(2022, 5, 17).and_hms_micro(13, 45, 7, 123_456),
+			Uuid::parse_str("8a1c2c4e-5f2b-4b0e-9a35-6c1d2f6e7b80").unwrap(),
+		);
+
+		let encoded = cursor.encode();
+		assert!(encoded
+			.chars()
+			.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+		assert_eq!(Cursor::decode(&encoded), Some(cursor));
+	}
+
+	#[test]
+	fn round_trips_min_and_pre_epoch_cursors() {
+		assert_eq!(Cursor::decode(&Cursor::min().encode()), Some(Cursor::min()));
+
+		let cursor = Cursor::new(
+			NaiveDate::from_ymd(1969, 12, 31).and_hms_micro(23, 59, 59, 999_999),
+			Uuid::nil(),
+		);
+		assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+	}
+
+	#[test]
+	fn drops_sub_microsecond_precision() {
+		let timestamp = NaiveDate::from_ymd(2022, 5, 17).and_hms_nano(0, 0, 0, 1_234_567);
+		let cursor = Cursor::new(timestamp, Uuid::nil());
+
+		let decoded = Cursor::decode(&cursor.encode()).unwrap();
+		assert_eq!(decoded.timestamp.timestamp_subsec_nanos(), 1_234_000);
+	}
+
+	#[test]
+	fn rejects_malformed_cursors() {
+		let encoded = Cursor::min().encode();
+
+		assert_eq!(Cursor::decode(""), None);
+		assert_eq!(Cursor::decode("not a cursor"), None);
+		assert_eq!(Cursor::decode(&encoded[..encoded.len() - 2]), None);
+		assert_eq!(Cursor::decode(&format!("{}AA", encoded)), None);
+	}
+}
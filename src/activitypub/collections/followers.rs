@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use super::{ItemXsdString, Items, Provider};
+use super::{Cursor, ItemXsdString, Items, Provider};
 use crate::error::ApiError;
 use crate::state::AppState;
 use crate::url;
@@ -42,9 +42,10 @@ impl Followers {
 
 	fn query_to_item(&self, row: PgRow) -> ItemXsdString {
 		let following_since: NaiveDateTime = row.get(0);
-		let username: &str = row.get(1);
-		let this_instance: bool = row.get(2);
-		let instance_url: Option<String> = row.get(3);
+		let user_id: Uuid = row.get(1);
+		let username: &str = row.get(2);
+		let this_instance: bool = row.get(3);
+		let instance_url: Option<String> = row.get(4);
 
 		let url = if this_instance {
 			url::activitypub_actor(username)
@@ -53,7 +54,7 @@ impl Followers {
 		};
 
 		ItemXsdString {
-			id: following_since.timestamp_millis(),
+			cursor: Cursor::new(following_since, user_id),
 			data: url,
 		}
 	}
@@ -84,9 +85,10 @@ impl Provider for Followers {
 		Ok(total_items)
 	}
 
-	async fn fetch_first_page(&self, data: &Self::Data) -> Result<Items, Self::Error> {
-		let items: Vec<ItemXsdString> = sqlx::query("SELECT follows.following_since, users.username, users.this_instance, users.instance_url FROM follows, users WHERE follows.object_user_id = $1 AND follows.subject_user_id = users.id AND pending = FALSE ORDER BY follows.following_since DESC LIMIT 20")
+	async fn fetch_first_page(&self, limit: i64, data: &Self::Data) -> Result<Items, Self::Error> {
+		let items: Vec<ItemXsdString> = sqlx::query("SELECT follows.following_since, users.id, users.username, users.this_instance, users.instance_url FROM follows, users WHERE follows.object_user_id = $1 AND follows.subject_user_id = users.id AND pending = FALSE ORDER BY follows.following_since DESC, users.id DESC LIMIT $2")
 			.bind(data.user_id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?;
@@ -94,10 +96,17 @@ impl Provider for Followers {
 		Ok(Items::XsdString(items))
 	}
 
-	async fn fetch_max_id(&self, max_id: i64, data: &Self::Data) -> Result<Items, Self::Error> {
-		let items: Vec<ItemXsdString> = sqlx::query("SELECT follows.following_since, users.username, users.this_instance, users.instance_url FROM follows, users WHERE follows.object_user_id = $1 AND follows.subject_user_id = users.id AND pending = FALSE AND follows.following_since < $2 ORDER BY follows.following_since DESC LIMIT 20")
+	async fn fetch_max_id(
+		&self,
+		max_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Vec<ItemXsdString> = sqlx::query("SELECT follows.following_since, users.id, users.username, users.this_instance, users.instance_url FROM follows, users WHERE follows.object_user_id = $1 AND follows.subject_user_id = users.id AND pending = FALSE AND (follows.following_since, users.id) < ($2, $3) ORDER BY follows.following_since DESC, users.id DESC LIMIT $4")
 			.bind(data.user_id)
-			.bind(max_id)
+			.bind(max_id.timestamp)
+			.bind(max_id.id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?;
@@ -105,10 +114,17 @@ impl Provider for Followers {
 		Ok(Items::XsdString(items))
 	}
 
-	async fn fetch_min_id(&self, min_id: i64, data: &Self::Data) -> Result<Items, Self::Error> {
-		let items: Vec<ItemXsdString> = sqlx::query("SELECT * FROM (SELECT follows.following_since, users.username, users.this_instance, users.instance_url FROM follows, users WHERE follows.object_user_id = $1 AND follows.subject_user_id = users.id AND pending = FALSE AND follows.following_since > $2 ORDER BY follows.following_since ASC LIMIT 20) AS tmp ORDER BY following_since DESC")
+	async fn fetch_min_id(
+		&self,
+		min_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Vec<ItemXsdString> = sqlx::query("SELECT * FROM (SELECT follows.following_since, users.id, users.username, users.this_instance, users.instance_url FROM follows, users WHERE follows.object_user_id = $1 AND follows.subject_user_id = users.id AND pending = FALSE AND (follows.following_since, users.id) > ($2, $3) ORDER BY follows.following_since ASC, users.id ASC LIMIT $4) AS tmp ORDER BY following_since DESC, id DESC")
 			.bind(data.user_id)
-			.bind(min_id)
+			.bind(min_id.timestamp)
+			.bind(min_id.id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?;
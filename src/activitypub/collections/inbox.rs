@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use super::{ItemBaseBox, Items, Provider};
+use super::{Cursor, ItemBaseBox, Items, Provider};
 use crate::error::ApiError;
 use crate::state::AppState;
 use crate::url;
@@ -43,51 +43,38 @@ impl Inbox {
 
 	fn query_to_item(&self, row: PgRow) -> Result<ItemBaseBox, ApiError> {
 		let published_at: NaiveDateTime = row.get(0);
-		let activity: Result<BaseBox, _> = serde_json::from_value(row.get(1));
+		let activity_id: Uuid = row.get(1);
+		let activity: Result<BaseBox, _> = serde_json::from_value(row.get(2));
 
 		if let Ok(activity) = activity {
 			Ok(ItemBaseBox {
-				id: published_at.timestamp_millis(),
+				cursor: Cursor::new(published_at, activity_id),
 				data: activity,
 			})
 		} else {
 			Err(ApiError::InternalServerError)
 		}
 	}
-}
-
-#[async_trait(?Send)]
-impl Provider for Inbox {
-	type Error = ApiError;
-	type Data = Data;
-
-	fn activitypub_id(&self, data: &Self::Data) -> Cow<'_, str> {
-		Cow::Owned(format!("{}/inbox", url::activitypub_actor(&data.username)))
-	}
 
-	async fn total_items(&self, data: &Self::Data) -> Result<u64, Self::Error> {
-		const FOLLOWING_QUERY: &str = "
-			SELECT ARRAY(SELECT object_user_id FROM follows WHERE subject_user_id = $1 AND pending = FALSE)
-		";
+	// The methods below are shared with list timelines, which are the home
+	// feed limited to activities by members of a list.
 
-		const MAIN_QUERY: &str = "
+	pub(super) async fn count(
+		&self,
+		user_id: Uuid,
+		list_id: Option<Uuid>,
+	) -> Result<u64, ApiError> {
+		const QUERY: &str = "
 			SELECT COUNT(1)
-			FROM activities
-			WHERE ($1 = ANY(to_mentions))
-			OR ($1 = ANY(cc_mentions))
-			OR (to_followers_of && $2)
-			OR (cc_followers_of && $2)
+			FROM feed_entries, activities
+			WHERE feed_entries.user_id = $1
+			AND activities.id = feed_entries.activity_id
+			AND ($2::uuid IS NULL OR activities.user_id IN (SELECT member_user_id FROM list_members WHERE list_id = $2))
 		";
 
-		let following: Vec<Uuid> = sqlx::query(FOLLOWING_QUERY)
-			.bind(data.user_id)
-			.fetch_one(&self.state.db)
-			.await?
-			.get(0);
-
-		let total_items: i64 = sqlx::query(MAIN_QUERY)
-			.bind(data.user_id)
-			.bind(following)
+		let total_items: i64 = sqlx::query(QUERY)
+			.bind(user_id)
+			.bind(list_id)
 			.fetch_one(&self.state.db)
 			.await?
 			.get(0);
@@ -96,31 +83,26 @@ impl Provider for Inbox {
 		Ok(total_items)
 	}
 
-	async fn fetch_first_page(&self, data: &Self::Data) -> Result<Items, Self::Error> {
-		const FOLLOWING_QUERY: &str = "
-			SELECT ARRAY(SELECT object_user_id FROM follows WHERE subject_user_id = $1 AND pending = FALSE)
-		";
-
-		const MAIN_QUERY: &str = "
-			SELECT published_at, activity
-			FROM activities
-			WHERE ($1 = ANY(to_mentions))
-			OR ($1 = ANY(cc_mentions))
-			OR (to_followers_of && $2)
-			OR (cc_followers_of && $2)
-			ORDER BY published_at DESC
-			LIMIT 20
+	pub(super) async fn first_page(
+		&self,
+		user_id: Uuid,
+		list_id: Option<Uuid>,
+		limit: i64,
+	) -> Result<Items, ApiError> {
+		const QUERY: &str = "
+			SELECT feed_entries.published_at, feed_entries.activity_id, activities.activity
+			FROM feed_entries, activities
+			WHERE feed_entries.user_id = $1
+			AND activities.id = feed_entries.activity_id
+			AND ($2::uuid IS NULL OR activities.user_id IN (SELECT member_user_id FROM list_members WHERE list_id = $2))
+			ORDER BY feed_entries.published_at DESC, feed_entries.activity_id DESC
+			LIMIT $3
 		";
 
-		let following: Vec<Uuid> = sqlx::query(FOLLOWING_QUERY)
-			.bind(data.user_id)
-			.fetch_one(&self.state.db)
-			.await?
-			.get(0);
-
-		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(MAIN_QUERY)
-			.bind(data.user_id)
-			.bind(following)
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(QUERY)
+			.bind(user_id)
+			.bind(list_id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?
@@ -130,36 +112,30 @@ impl Provider for Inbox {
 		Ok(Items::BaseBox(items?))
 	}
 
-	async fn fetch_max_id(&self, max_id: i64, data: &Self::Data) -> Result<Items, Self::Error> {
-		let max_id =
-			NaiveDateTime::from_timestamp(max_id / 1000, u32::try_from((max_id % 1000) * 1000000)?);
-
-		const FOLLOWING_QUERY: &str = "
-			SELECT ARRAY(SELECT object_user_id FROM follows WHERE subject_user_id = $1 AND pending = FALSE)
-		";
-
-		const MAIN_QUERY: &str = "
-			SELECT published_at, activity
-			FROM activities
-			WHERE (($1 = ANY(to_mentions))
-			OR ($1 = ANY(cc_mentions))
-			OR (to_followers_of && $2)
-			OR (cc_followers_of && $2))
-			AND published_at < $3
-			ORDER BY published_at DESC
-			LIMIT 20
+	pub(super) async fn max_id(
+		&self,
+		user_id: Uuid,
+		list_id: Option<Uuid>,
+		max_id: Cursor,
+		limit: i64,
+	) -> Result<Items, ApiError> {
+		const QUERY: &str = "
+			SELECT feed_entries.published_at, feed_entries.activity_id, activities.activity
+			FROM feed_entries, activities
+			WHERE feed_entries.user_id = $1
+			AND (feed_entries.published_at, feed_entries.activity_id) < ($3, $4)
+			AND activities.id = feed_entries.activity_id
+			AND ($2::uuid IS NULL OR activities.user_id IN (SELECT member_user_id FROM list_members WHERE list_id = $2))
+			ORDER BY feed_entries.published_at DESC, feed_entries.activity_id DESC
+			LIMIT $5
 		";
 
-		let following: Vec<Uuid> = sqlx::query(FOLLOWING_QUERY)
-			.bind(data.user_id)
-			.fetch_one(&self.state.db)
-			.await?
-			.get(0);
-
-		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(MAIN_QUERY)
-			.bind(data.user_id)
-			.bind(following)
-			.bind(max_id)
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(QUERY)
+			.bind(user_id)
+			.bind(list_id)
+			.bind(max_id.timestamp)
+			.bind(max_id.id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?
@@ -169,40 +145,34 @@ impl Provider for Inbox {
 		Ok(Items::BaseBox(items?))
 	}
 
-	async fn fetch_min_id(&self, min_id: i64, data: &Self::Data) -> Result<Items, Self::Error> {
-		let min_id =
-			NaiveDateTime::from_timestamp(min_id / 1000, u32::try_from((min_id % 1000) * 1000000)?);
-
-		const FOLLOWING_QUERY: &str = "
-			SELECT ARRAY(SELECT object_user_id FROM follows WHERE subject_user_id = $1 AND pending = FALSE)
-		";
-
-		const MAIN_QUERY: &str = "
+	pub(super) async fn min_id(
+		&self,
+		user_id: Uuid,
+		list_id: Option<Uuid>,
+		min_id: Cursor,
+		limit: i64,
+	) -> Result<Items, ApiError> {
+		const QUERY: &str = "
 			SELECT *
 			FROM
-			(SELECT published_at, activity
-			FROM activities
-			WHERE (($1 = ANY(to_mentions))
-			OR ($1 = ANY(cc_mentions))
-			OR (to_followers_of && $2)
-			OR (cc_followers_of && $2))
-			AND published_at > $3
-			ORDER BY published_at ASC
-			LIMIT 20)
+			(SELECT feed_entries.published_at, feed_entries.activity_id, activities.activity
+			FROM feed_entries, activities
+			WHERE feed_entries.user_id = $1
+			AND (feed_entries.published_at, feed_entries.activity_id) > ($3, $4)
+			AND activities.id = feed_entries.activity_id
+			AND ($2::uuid IS NULL OR activities.user_id IN (SELECT member_user_id FROM list_members WHERE list_id = $2))
+			ORDER BY feed_entries.published_at ASC, feed_entries.activity_id ASC
+			LIMIT $5)
 			AS tmp
-			ORDER BY published_at DESC
+			ORDER BY published_at DESC, activity_id DESC
 		";
 
-		let following: Vec<Uuid> = sqlx::query(FOLLOWING_QUERY)
-			.bind(data.user_id)
-			.fetch_one(&self.state.db)
-			.await?
-			.get(0);
-
-		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(MAIN_QUERY)
-			.bind(data.user_id)
-			.bind(following)
-			.bind(min_id)
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(QUERY)
+			.bind(user_id)
+			.bind(list_id)
+			.bind(min_id.timestamp)
+			.bind(min_id.id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?
@@ -212,3 +182,39 @@ impl Provider for Inbox {
 		Ok(Items::BaseBox(items?))
 	}
 }
+
+#[async_trait(?Send)]
+impl Provider for Inbox {
+	type Error = ApiError;
+	type Data = Data;
+
+	fn activitypub_id(&self, data: &Self::Data) -> Cow<'_, str> {
+		Cow::Owned(format!("{}/inbox", url::activitypub_actor(&data.username)))
+	}
+
+	async fn total_items(&self, data: &Self::Data) -> Result<u64, Self::Error> {
+		self.count(data.user_id, None).await
+	}
+
+	async fn fetch_first_page(&self, limit: i64, data: &Self::Data) -> Result<Items, Self::Error> {
+		self.first_page(data.user_id, None, limit).await
+	}
+
+	async fn fetch_max_id(
+		&self,
+		max_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		self.max_id(data.user_id, None, max_id, limit).await
+	}
+
+	async fn fetch_min_id(
+		&self,
+		min_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		self.min_id(data.user_id, None, min_id, limit).await
+	}
+}
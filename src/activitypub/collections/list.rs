@@ -0,0 +1,87 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::inbox::Inbox;
+use super::{Cursor, Items, Provider};
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::url;
+use actix_web::web;
+use async_trait::async_trait;
+use std::borrow::Cow;
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+pub struct Data {
+	pub user_id: Uuid,
+	pub username: String,
+	pub list_id: Uuid,
+}
+
+/// Home feed activities by members of a list, newest first.
+#[derive(Clone)]
+pub struct List {
+	inbox: Inbox,
+}
+
+impl List {
+	pub fn new(state: web::Data<AppState>) -> Self {
+		Self {
+			inbox: Inbox::new(state),
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Provider for List {
+	type Error = ApiError;
+	type Data = Data;
+
+	fn activitypub_id(&self, data: &Self::Data) -> Cow<'_, str> {
+		Cow::Owned(url::list(&data.username, data.list_id))
+	}
+
+	async fn total_items(&self, data: &Self::Data) -> Result<u64, Self::Error> {
+		self.inbox.count(data.user_id, Some(data.list_id)).await
+	}
+
+	async fn fetch_first_page(&self, limit: i64, data: &Self::Data) -> Result<Items, Self::Error> {
+		self.inbox
+			.first_page(data.user_id, Some(data.list_id), limit)
+			.await
+	}
+
+	async fn fetch_max_id(
+		&self,
+		max_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		self.inbox
+			.max_id(data.user_id, Some(data.list_id), max_id, limit)
+			.await
+	}
+
+	async fn fetch_min_id(
+		&self,
+		min_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		self.inbox
+			.min_id(data.user_id, Some(data.list_id), min_id, limit)
+			.await
+	}
+}
@@ -13,12 +13,18 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+pub mod cursor;
 pub mod followers;
 pub mod following;
 pub mod inbox;
+pub mod list;
 pub mod outbox;
+pub mod remixes;
 pub mod stream;
+pub mod tag;
+pub mod timeline;
 
+pub use cursor::Cursor;
 pub use stream::Stream;
 
 use crate::error::ApiError;
@@ -26,21 +32,38 @@ use activitystreams::collection::properties::{CollectionPageProperties, Collecti
 use activitystreams::collection::{OrderedCollection, OrderedCollectionPage};
 use activitystreams::object::properties::ObjectProperties;
 use activitystreams::BaseBox;
+use actix_web::{web, Either};
 use async_trait::async_trait;
+use serde::Deserialize;
 use std::borrow::Cow;
 use std::error::Error as StdError;
 use std::fmt::Debug;
 use tracing::{error, instrument};
 
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+pub const MAX_PAGE_SIZE: u32 = 100;
+
+pub type CollectionResponse =
+	Either<web::Json<OrderedCollection>, web::Json<OrderedCollectionPage>>;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PageQuery {
+	#[serde(default)]
+	pub page: bool,
+	pub max_id: Option<String>,
+	pub min_id: Option<String>,
+	pub limit: Option<u32>,
+}
+
 #[derive(Clone, Debug)]
 pub struct ItemBaseBox {
-	pub id: i64,
+	pub cursor: Cursor,
 	pub data: BaseBox,
 }
 
 #[derive(Clone, Debug)]
 pub struct ItemXsdString {
-	pub id: i64,
+	pub cursor: Cursor,
 	pub data: String,
 }
 
@@ -71,31 +94,66 @@ impl Items {
 		}
 	}
 
-	fn first_id(&self) -> Option<i64> {
+	fn first_cursor(&self) -> Option<Cursor> {
+		match self {
+			Self::BaseBox(v) => v.first().map(|val| val.cursor),
+			Self::XsdString(v) => v.first().map(|val| val.cursor),
+		}
+	}
+
+	fn last_cursor(&self) -> Option<Cursor> {
 		match self {
-			Self::BaseBox(v) => v.first().map(|val| val.id),
-			Self::XsdString(v) => v.first().map(|val| val.id),
+			Self::BaseBox(v) => v.last().map(|val| val.cursor),
+			Self::XsdString(v) => v.last().map(|val| val.cursor),
 		}
 	}
 
-	fn last_id(&self) -> Option<i64> {
+	fn truncate(&mut self, len: usize) {
 		match self {
-			Self::BaseBox(v) => v.last().map(|val| val.id),
-			Self::XsdString(v) => v.last().map(|val| val.id),
+			Self::BaseBox(v) => v.truncate(len),
+			Self::XsdString(v) => v.truncate(len),
+		}
+	}
+
+	fn truncate_front(&mut self, len: usize) {
+		match self {
+			Self::BaseBox(v) => {
+				v.drain(..v.len().saturating_sub(len));
+			}
+			Self::XsdString(v) => {
+				v.drain(..v.len().saturating_sub(len));
+			}
 		}
 	}
 }
 
+/// Items are returned newest first. `fetch_max_id` returns items before the
+/// cursor and `fetch_min_id` the ones right after it.
 #[async_trait(?Send)]
 pub trait Provider {
 	type Error: StdError;
 	type Data;
 
 	fn activitypub_id<'a>(&'a self, data: &'a Self::Data) -> Cow<'a, str>;
+
+	fn default_page_size(&self) -> u32 {
+		DEFAULT_PAGE_SIZE
+	}
+
 	async fn total_items(&self, data: &Self::Data) -> Result<u64, Self::Error>;
-	async fn fetch_first_page(&self, data: &Self::Data) -> Result<Items, Self::Error>;
-	async fn fetch_max_id(&self, max_id: i64, data: &Self::Data) -> Result<Items, Self::Error>;
-	async fn fetch_min_id(&self, min_id: i64, data: &Self::Data) -> Result<Items, Self::Error>;
+	async fn fetch_first_page(&self, limit: i64, data: &Self::Data) -> Result<Items, Self::Error>;
+	async fn fetch_max_id(
+		&self,
+		max_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error>;
+	async fn fetch_min_id(
+		&self,
+		min_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error>;
 }
 
 #[derive(Debug)]
@@ -105,6 +163,7 @@ where
 	<T as Provider>::Data: Debug,
 {
 	provider: T,
+	page_size: u32,
 }
 
 impl<T> Collection<T>
@@ -113,12 +172,50 @@ where
 	<T as Provider>::Data: Debug,
 {
 	pub fn new(provider: T) -> Self {
-		Self { provider }
+		let page_size = provider.default_page_size();
+		Self {
+			provider,
+			page_size,
+		}
+	}
+
+	/// `None` keeps the default of the provider.
+	pub fn with_page_size(mut self, page_size: Option<u32>) -> Result<Self, ApiError> {
+		match page_size {
+			Some(page_size) if page_size == 0 || page_size > MAX_PAGE_SIZE => {
+				return Err(ApiError::OtherBadRequest);
+			}
+			Some(page_size) => self.page_size = page_size,
+			None => (),
+		}
+
+		Ok(self)
+	}
+
+	/// Returns the collection itself unless the query asks for a page.
+	#[instrument(skip(self))]
+	pub async fn respond(
+		&self,
+		query: &PageQuery,
+		data: &<T as Provider>::Data,
+	) -> Result<CollectionResponse, ApiError> {
+		if !query.page {
+			return Ok(Either::Left(web::Json(self.index_page(data).await?)));
+		}
+
+		let page = match (&query.max_id, &query.min_id) {
+			(None, None) => self.first_page(data).await?,
+			(Some(max_id), None) => self.max_id_page(max_id, data).await?,
+			(None, Some(min_id)) => self.min_id_page(min_id, data).await?,
+			(Some(_), Some(_)) => return Err(ApiError::OtherBadRequest),
+		};
+
+		Ok(Either::Right(web::Json(page)))
 	}
 
 	#[allow(dead_code)]
 	pub fn stream<'a>(&'a self, data: &'a <T as Provider>::Data) -> Stream<'a, T> {
-		Stream::new(&self.provider, data)
+		Stream::new(&self.provider, data, i64::from(self.page_size))
 	}
 
 	#[instrument(skip(self))]
@@ -150,8 +247,9 @@ where
 		let collection_props: &mut CollectionProperties = collection.as_mut();
 
 		collection_props.set_total_items(self.len(data).await?)?;
-		collection_props.set_first_xsd_any_uri(format!("{}?page=true", id))?;
-		collection_props.set_last_xsd_any_uri(format!("{}?min_id=0&page=true", id))?;
+		collection_props.set_first_xsd_any_uri(self.page_url(&id, None))?;
+		collection_props
+			.set_last_xsd_any_uri(self.page_url(&id, Some(("min_id", Cursor::min()))))?;
 
 		Ok(collection)
 	}
@@ -161,73 +259,116 @@ where
 		&self,
 		data: &<T as Provider>::Data,
 	) -> Result<OrderedCollectionPage, ApiError> {
-		let (part_of, mut page) = self.prepare_page(data)?;
-		let items = self.provider.fetch_first_page(data).await.map_err(|err| {
-			error!(?err, "Failed to fetch the first page of a collection");
-			ApiError::InternalServerError
-		})?;
+		let part_of = self.id(data);
+		let mut page = self.prepare_page(&part_of, None)?;
 
-		self.add_next_prev_and_finalize(&mut page, items, &part_of, false)?;
+		// One more item than needed is fetched to find out whether there is
+		// a next page.
+		let mut items = self
+			.provider
+			.fetch_first_page(self.fetch_limit(), data)
+			.await
+			.map_err(|err| {
+				error!(?err, "Failed to fetch the first page of a collection");
+				ApiError::InternalServerError
+			})?;
+		let has_next = self.has_more(&items);
+		items.truncate(self.page_size as usize);
+
+		self.add_next_prev_and_finalize(&mut page, items, &part_of, has_next, false)?;
 		Ok(page)
 	}
 
 	#[instrument(skip(self))]
 	pub async fn max_id_page(
 		&self,
-		max_id: i64,
+		max_id: &str,
 		data: &<T as Provider>::Data,
 	) -> Result<OrderedCollectionPage, ApiError> {
-		let (part_of, mut page) = self.prepare_page(data)?;
-		let items = self
+		let max_id = Cursor::decode(max_id).ok_or(ApiError::OtherBadRequest)?;
+		let part_of = self.id(data);
+		let mut page = self.prepare_page(&part_of, Some(("max_id", max_id)))?;
+
+		let mut items = self
 			.provider
-			.fetch_max_id(max_id, data)
+			.fetch_max_id(max_id, self.fetch_limit(), data)
 			.await
 			.map_err(|err| {
 				error!(?err, "Failed to fetch max_id page of a collection");
 				ApiError::InternalServerError
 			})?;
+		let has_next = self.has_more(&items);
+		items.truncate(self.page_size as usize);
 
-		self.add_next_prev_and_finalize(&mut page, items, &part_of, true)?;
+		// The item at `max_id` is newer than the ones on this page.
+		self.add_next_prev_and_finalize(&mut page, items, &part_of, has_next, true)?;
 		Ok(page)
 	}
 
 	#[instrument(skip(self))]
 	pub async fn min_id_page(
 		&self,
-		min_id: i64,
+		min_id: &str,
 		data: &<T as Provider>::Data,
 	) -> Result<OrderedCollectionPage, ApiError> {
-		let (part_of, mut page) = self.prepare_page(data)?;
-		let items = self
+		let min_id = Cursor::decode(min_id).ok_or(ApiError::OtherBadRequest)?;
+		let part_of = self.id(data);
+		let mut page = self.prepare_page(&part_of, Some(("min_id", min_id)))?;
+
+		// Here the extra item is the newest one, so it's at the front.
+		let mut items = self
 			.provider
-			.fetch_min_id(min_id, data)
+			.fetch_min_id(min_id, self.fetch_limit(), data)
 			.await
 			.map_err(|err| {
 				error!(?err, "Failed to fetch min_id page of a collection");
 				ApiError::InternalServerError
 			})?;
+		let has_prev = self.has_more(&items);
+		items.truncate_front(self.page_size as usize);
 
-		self.add_next_prev_and_finalize(&mut page, items, &part_of, true)?;
+		// The item at `min_id`, if any, is older than the ones on this page.
+		let has_next = min_id != Cursor::min();
+		self.add_next_prev_and_finalize(&mut page, items, &part_of, has_next, has_prev)?;
 		Ok(page)
 	}
 
-	#[instrument(skip(self))]
-	fn prepare_page<'a>(
-		&'a self,
-		data: &'a <T as Provider>::Data,
-	) -> Result<(Cow<'a, str>, OrderedCollectionPage), ApiError> {
-		let part_of = self.id(data);
+	fn fetch_limit(&self) -> i64 {
+		i64::from(self.page_size) + 1
+	}
+
+	fn has_more(&self, items: &Items) -> bool {
+		items.len() > self.page_size as usize
+	}
+
+	fn page_url(&self, part_of: &str, cursor: Option<(&str, Cursor)>) -> String {
+		let mut url = match cursor {
+			Some((name, cursor)) => format!("{}?{}={}&page=true", part_of, name, cursor.encode()),
+			None => format!("{}?page=true", part_of),
+		};
+		if self.page_size != self.provider.default_page_size() {
+			url.push_str(&format!("&limit={}", self.page_size));
+		}
 
+		url
+	}
+
+	#[instrument(skip(self))]
+	fn prepare_page(
+		&self,
+		part_of: &str,
+		cursor: Option<(&str, Cursor)>,
+	) -> Result<OrderedCollectionPage, ApiError> {
 		let mut page = OrderedCollectionPage::new();
 		let page_object_props: &mut ObjectProperties = page.as_mut();
 
 		page_object_props.set_context_xsd_any_uri("https://www.w3.org/ns/activitystreams")?;
-		page_object_props.set_id(format!("{}?page=true", part_of))?;
+		page_object_props.set_id(self.page_url(part_of, cursor))?;
 
 		let page_props: &mut CollectionPageProperties = page.as_mut();
-		page_props.set_part_of_xsd_any_uri(&*part_of)?;
+		page_props.set_part_of_xsd_any_uri(part_of)?;
 
-		Ok((part_of, page))
+		Ok(page)
 	}
 
 	#[instrument(skip(self, page, items))]
@@ -236,21 +377,21 @@ where
 		page: &mut OrderedCollectionPage,
 		items: Items,
 		part_of: &str,
-		do_add_prev_page: bool,
+		has_next: bool,
+		has_prev: bool,
 	) -> Result<(), ApiError> {
 		if !items.is_empty() {
-			let last_id = items.last_id().unwrap();
+			let page_props: &mut CollectionPageProperties = page.as_mut();
 
-			if do_add_prev_page {
-				let page_props: &mut CollectionPageProperties = page.as_mut();
-				let first_id = items.first_id().unwrap();
+			if has_prev {
+				let first_cursor = items.first_cursor().unwrap();
 				page_props
-					.set_prev_xsd_any_uri(format!("{}?min_id={}&page=true", part_of, first_id))?;
+					.set_prev_xsd_any_uri(self.page_url(part_of, Some(("min_id", first_cursor))))?;
 			}
-			if items.len() == 20 {
-				let page_props: &mut CollectionPageProperties = page.as_mut();
+			if has_next {
+				let last_cursor = items.last_cursor().unwrap();
 				page_props
-					.set_next_xsd_any_uri(format!("{}?max_id={}&page=true", part_of, last_id))?;
+					.set_next_xsd_any_uri(self.page_url(part_of, Some(("max_id", last_cursor))))?;
 			}
 
 			let page_props: &mut CollectionProperties = page.as_mut();
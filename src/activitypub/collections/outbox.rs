@@ -13,7 +13,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use super::{ItemBaseBox, Items, Provider};
+use super::{Cursor, ItemBaseBox, Items, Provider};
+use crate::audience::{Viewer, VISIBLE_TO_VIEWER};
 use crate::error::ApiError;
 use crate::state::AppState;
 use crate::url;
@@ -29,6 +30,7 @@ use uuid::Uuid;
 pub struct Data {
 	pub user_id: Uuid,
 	pub username: String,
+	pub viewer: Viewer,
 }
 
 #[derive(Clone)]
@@ -43,11 +45,12 @@ impl Outbox {
 
 	fn query_to_item(&self, row: PgRow) -> Result<ItemBaseBox, ApiError> {
 		let published_at: NaiveDateTime = row.get(0);
-		let activity: Result<BaseBox, _> = serde_json::from_value(row.get(1));
+		let id: Uuid = row.get(1);
+		let activity: Result<BaseBox, _> = serde_json::from_value(row.get(2));
 
 		if let Ok(activity) = activity {
 			Ok(ItemBaseBox {
-				id: published_at.timestamp_millis(),
+				cursor: Cursor::new(published_at, id),
 				data: activity,
 			})
 		} else {
@@ -66,19 +69,25 @@ impl Provider for Outbox {
 	}
 
 	async fn total_items(&self, data: &Self::Data) -> Result<u64, Self::Error> {
-		let total_items: i64 = sqlx::query("SELECT COUNT(1) FROM activities WHERE user_id = $1")
-			.bind(data.user_id)
-			.fetch_one(&self.state.db)
-			.await?
-			.get(0);
+		let total_items: i64 = sqlx::query(&format!(
+			"SELECT COUNT(1) FROM activities WHERE activities.user_id = $1 AND {}",
+			VISIBLE_TO_VIEWER
+		))
+		.bind(data.user_id)
+		.bind(data.viewer.user_id())
+		.fetch_one(&self.state.db)
+		.await?
+		.get(0);
 
 		let total_items = u64::try_from(total_items).expect("expected count to be zero or more");
 		Ok(total_items)
 	}
 
-	async fn fetch_first_page(&self, data: &Self::Data) -> Result<Items, Self::Error> {
-		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query("SELECT published_at, activity FROM activities WHERE user_id = $1 ORDER BY published_at DESC LIMIT 20")
+	async fn fetch_first_page(&self, limit: i64, data: &Self::Data) -> Result<Items, Self::Error> {
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(&format!("SELECT published_at, id, activity FROM activities WHERE activities.user_id = $1 AND {} ORDER BY published_at DESC, id DESC LIMIT $3", VISIBLE_TO_VIEWER))
 			.bind(data.user_id)
+			.bind(data.viewer.user_id())
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?
@@ -88,12 +97,18 @@ impl Provider for Outbox {
 		Ok(Items::BaseBox(items?))
 	}
 
-	async fn fetch_max_id(&self, max_id: i64, data: &Self::Data) -> Result<Items, Self::Error> {
-		let max_id =
-			NaiveDateTime::from_timestamp(max_id / 1000, u32::try_from((max_id % 1000) * 1000000)?);
-		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query("SELECT published_at, activity FROM activities WHERE user_id = $1 AND published_at < $2 ORDER BY published_at DESC LIMIT 20")
+	async fn fetch_max_id(
+		&self,
+		max_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(&format!("SELECT published_at, id, activity FROM activities WHERE activities.user_id = $1 AND {} AND (published_at, id) < ($3, $4) ORDER BY published_at DESC, id DESC LIMIT $5", VISIBLE_TO_VIEWER))
 			.bind(data.user_id)
-			.bind(max_id)
+			.bind(data.viewer.user_id())
+			.bind(max_id.timestamp)
+			.bind(max_id.id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?
@@ -103,12 +118,18 @@ impl Provider for Outbox {
 		Ok(Items::BaseBox(items?))
 	}
 
-	async fn fetch_min_id(&self, min_id: i64, data: &Self::Data) -> Result<Items, Self::Error> {
-		let min_id =
-			NaiveDateTime::from_timestamp(min_id / 1000, u32::try_from((min_id % 1000) * 1000000)?);
-		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query("SELECT * FROM (SELECT published_at, activity FROM activities WHERE user_id = $1 AND published_at > $2 ORDER BY published_at LIMIT 20) AS tmp ORDER BY published_at DESC")
+	async fn fetch_min_id(
+		&self,
+		min_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query(&format!("SELECT * FROM (SELECT published_at, id, activity FROM activities WHERE activities.user_id = $1 AND {} AND (published_at, id) > ($3, $4) ORDER BY published_at ASC, id ASC LIMIT $5) AS tmp ORDER BY published_at DESC, id DESC", VISIBLE_TO_VIEWER))
 			.bind(data.user_id)
-			.bind(min_id)
+			.bind(data.viewer.user_id())
+			.bind(min_id.timestamp)
+			.bind(min_id.id)
+			.bind(limit)
 			.map(|row| self.query_to_item(row))
 			.fetch_all(&self.state.db)
 			.await?
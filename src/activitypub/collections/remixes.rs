@@ -0,0 +1,124 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Cursor, ItemXsdString, Items, Provider};
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::url;
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sqlx::{postgres::PgRow, Row};
+use std::borrow::Cow;
+use uuid::Uuid;
+
+#[derive(Clone, Debug)]
+pub struct Data {
+	pub activity_id: Uuid,
+}
+
+/// Public memes derived from a meme, newest first.
+#[derive(Clone)]
+pub struct Remixes {
+	state: web::Data<AppState>,
+}
+
+impl Remixes {
+	pub fn new(state: web::Data<AppState>) -> Self {
+		Self { state }
+	}
+
+	fn query_to_item(&self, row: PgRow) -> ItemXsdString {
+		let published_at: NaiveDateTime = row.get(0);
+		let id: Uuid = row.get(1);
+		let object_id: String = row.get(2);
+
+		ItemXsdString {
+			cursor: Cursor::new(published_at, id),
+			data: object_id,
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Provider for Remixes {
+	type Error = ApiError;
+	type Data = Data;
+
+	fn activitypub_id(&self, data: &Self::Data) -> Cow<'_, str> {
+		Cow::Owned(format!(
+			"{}/remixes",
+			url::activitypub_object(data.activity_id)
+		))
+	}
+
+	async fn total_items(&self, data: &Self::Data) -> Result<u64, Self::Error> {
+		let total_items: i64 = sqlx::query("SELECT COUNT(1) FROM remixes, activities WHERE remixes.derived_from = $1 AND activities.id = remixes.activity_id AND activities.is_public = TRUE")
+			.bind(url::activitypub_object(data.activity_id))
+			.fetch_one(&self.state.db)
+			.await?
+			.get(0);
+
+		let total_items = u64::try_from(total_items).expect("expected count to be zero or more");
+		Ok(total_items)
+	}
+
+	async fn fetch_first_page(&self, limit: i64, data: &Self::Data) -> Result<Items, Self::Error> {
+		let items: Vec<ItemXsdString> = sqlx::query("SELECT activities.published_at, activities.id, activities.activity->'object'->>'id' FROM remixes, activities WHERE remixes.derived_from = $1 AND activities.id = remixes.activity_id AND activities.is_public = TRUE ORDER BY activities.published_at DESC, activities.id DESC LIMIT $2")
+			.bind(url::activitypub_object(data.activity_id))
+			.bind(limit)
+			.map(|row| self.query_to_item(row))
+			.fetch_all(&self.state.db)
+			.await?;
+
+		Ok(Items::XsdString(items))
+	}
+
+	async fn fetch_max_id(
+		&self,
+		max_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Vec<ItemXsdString> = sqlx::query("SELECT activities.published_at, activities.id, activities.activity->'object'->>'id' FROM remixes, activities WHERE remixes.derived_from = $1 AND activities.id = remixes.activity_id AND activities.is_public = TRUE AND (activities.published_at, activities.id) < ($2, $3) ORDER BY activities.published_at DESC, activities.id DESC LIMIT $4")
+			.bind(url::activitypub_object(data.activity_id))
+			.bind(max_id.timestamp)
+			.bind(max_id.id)
+			.bind(limit)
+			.map(|row| self.query_to_item(row))
+			.fetch_all(&self.state.db)
+			.await?;
+
+		Ok(Items::XsdString(items))
+	}
+
+	async fn fetch_min_id(
+		&self,
+		min_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Vec<ItemXsdString> = sqlx::query("SELECT * FROM (SELECT activities.published_at, activities.id, activities.activity->'object'->>'id' AS object_id FROM remixes, activities WHERE remixes.derived_from = $1 AND activities.id = remixes.activity_id AND activities.is_public = TRUE AND (activities.published_at, activities.id) > ($2, $3) ORDER BY activities.published_at ASC, activities.id ASC LIMIT $4) AS tmp ORDER BY published_at DESC, id DESC")
+			.bind(url::activitypub_object(data.activity_id))
+			.bind(min_id.timestamp)
+			.bind(min_id.id)
+			.bind(limit)
+			.map(|row| self.query_to_item(row))
+			.fetch_all(&self.state.db)
+			.await?;
+
+		Ok(Items::XsdString(items))
+	}
+}
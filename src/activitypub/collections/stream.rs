@@ -13,7 +13,7 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use super::{Item, Items, Provider};
+use super::{Cursor, Item, Items, Provider};
 use crate::error::ApiError;
 use futures::Stream as FuturesStream;
 use pin_project::pin_project;
@@ -34,7 +34,8 @@ where
 	provider: &'a T,
 	data: &'a <T as Provider>::Data,
 	items: VecDeque<Item>,
-	max_id: Option<i64>,
+	max_id: Option<Cursor>,
+	page_size: i64,
 	failed: bool,
 }
 
@@ -43,13 +44,14 @@ where
 	T: Provider,
 	<T as Provider>::Data: Debug,
 {
-	pub fn new(provider: &'a T, data: &'a <T as Provider>::Data) -> Self {
+	pub fn new(provider: &'a T, data: &'a <T as Provider>::Data, page_size: i64) -> Self {
 		Self {
-			fut: provider.fetch_first_page(data),
+			fut: provider.fetch_first_page(page_size, data),
 			provider,
 			data,
 			items: VecDeque::new(),
 			max_id: None,
+			page_size,
 			failed: false,
 		}
 	}
@@ -73,8 +75,10 @@ where
 			return Poll::Ready(Some(Ok(this.items.pop_front().unwrap())));
 		}
 
-		if let Some(max_id) = *this.max_id {
-			*this.fut = this.provider.fetch_max_id(max_id, this.data);
+		if let Some(max_id) = this.max_id.take() {
+			*this.fut = this
+				.provider
+				.fetch_max_id(max_id, *this.page_size, this.data);
 		}
 
 		match this.fut.as_mut().poll(cx) {
@@ -89,12 +93,12 @@ where
 						Poll::Ready(None)
 					} else {
 						let last_item = items.last().unwrap();
-						let last_id = match last_item {
-							Item::BaseBox(item) => item.id,
-							Item::XsdString(item) => item.id,
+						let last_cursor = match last_item {
+							Item::BaseBox(item) => item.cursor,
+							Item::XsdString(item) => item.cursor,
 						};
 
-						*this.max_id = Some(last_id);
+						*this.max_id = Some(last_cursor);
 						*this.items = items.into();
 						Poll::Ready(Some(Ok(this.items.pop_front().unwrap())))
 					}
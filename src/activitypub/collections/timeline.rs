@@ -0,0 +1,161 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{Cursor, ItemBaseBox, Items, Provider};
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::url;
+use activitystreams::BaseBox;
+use actix_web::web;
+use async_trait::async_trait;
+use chrono::NaiveDateTime;
+use sqlx::{postgres::PgRow, Row};
+use std::borrow::Cow;
+use uuid::Uuid;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Data {
+	/// Local-only memes are included for signed-in viewers.
+	Local {
+		include_local_only: bool,
+	},
+	Federated,
+}
+
+impl Data {
+	fn name(&self) -> &'static str {
+		match self {
+			Self::Local { .. } => "local",
+			Self::Federated => "federated",
+		}
+	}
+
+	fn is_local(&self) -> bool {
+		matches!(self, Self::Local { .. })
+	}
+
+	fn include_local_only(&self) -> bool {
+		matches!(
+			self,
+			Self::Local {
+				include_local_only: true
+			}
+		)
+	}
+}
+
+#[derive(Clone)]
+pub struct Timeline {
+	state: web::Data<AppState>,
+}
+
+impl Timeline {
+	pub fn new(state: web::Data<AppState>) -> Self {
+		Self { state }
+	}
+
+	fn query_to_item(&self, row: PgRow) -> Result<ItemBaseBox, ApiError> {
+		let published_at: NaiveDateTime = row.get(0);
+		let id: Uuid = row.get(1);
+		let activity: Result<BaseBox, _> = serde_json::from_value(row.get(2));
+
+		if let Ok(activity) = activity {
+			Ok(ItemBaseBox {
+				cursor: Cursor::new(published_at, id),
+				data: activity,
+			})
+		} else {
+			Err(ApiError::InternalServerError)
+		}
+	}
+}
+
+#[async_trait(?Send)]
+impl Provider for Timeline {
+	type Error = ApiError;
+	type Data = Data;
+
+	fn activitypub_id(&self, data: &Self::Data) -> Cow<'_, str> {
+		Cow::Owned(url::timeline(data.name()))
+	}
+
+	async fn total_items(&self, data: &Self::Data) -> Result<u64, Self::Error> {
+		let total_items: i64 = sqlx::query("SELECT COUNT(1) FROM activities WHERE (is_public = TRUE OR ($2 = TRUE AND is_local_only = TRUE)) AND activity->>'type' = 'Create' AND ($1 = FALSE OR this_instance = TRUE)")
+			.bind(data.is_local())
+			.bind(data.include_local_only())
+			.fetch_one(&self.state.db)
+			.await?
+			.get(0);
+
+		let total_items = u64::try_from(total_items).expect("expected count to be zero or more");
+		Ok(total_items)
+	}
+
+	async fn fetch_first_page(&self, limit: i64, data: &Self::Data) -> Result<Items, Self::Error> {
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query("SELECT published_at, id, activity FROM activities WHERE (is_public = TRUE OR ($2 = TRUE AND is_local_only = TRUE)) AND activity->>'type' = 'Create' AND ($1 = FALSE OR this_instance = TRUE) ORDER BY published_at DESC, id DESC LIMIT $3")
+			.bind(data.is_local())
+			.bind(data.include_local_only())
+			.bind(limit)
+			.map(|row| self.query_to_item(row))
+			.fetch_all(&self.state.db)
+			.await?
+			.into_iter()
+			.collect();
+
+		Ok(Items::BaseBox(items?))
+	}
+
+	async fn fetch_max_id(
+		&self,
+		max_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query("SELECT published_at, id, activity FROM activities WHERE (is_public = TRUE OR ($2 = TRUE AND is_local_only = TRUE)) AND activity->>'type' = 'Create' AND ($1 = FALSE OR this_instance = TRUE) AND (published_at, id) < ($3, $4) ORDER BY published_at DESC, id DESC LIMIT $5")
+			.bind(data.is_local())
+			.bind(data.include_local_only())
+			.bind(max_id.timestamp)
+			.bind(max_id.id)
+			.bind(limit)
+			.map(|row| self.query_to_item(row))
+			.fetch_all(&self.state.db)
+			.await?
+			.into_iter()
+			.collect();
+
+		Ok(Items::BaseBox(items?))
+	}
+
+	async fn fetch_min_id(
+		&self,
+		min_id: Cursor,
+		limit: i64,
+		data: &Self::Data,
+	) -> Result<Items, Self::Error> {
+		let items: Result<Vec<ItemBaseBox>, ApiError> = sqlx::query("SELECT * FROM (SELECT published_at, id, activity FROM activities WHERE (is_public = TRUE OR ($2 = TRUE AND is_local_only = TRUE)) AND activity->>'type' = 'Create' AND ($1 = FALSE OR this_instance = TRUE) AND (published_at, id) > ($3, $4) ORDER BY published_at ASC, id ASC LIMIT $5) AS tmp ORDER BY published_at DESC, id DESC")
+			.bind(data.is_local())
+			.bind(data.include_local_only())
+			.bind(min_id.timestamp)
+			.bind(min_id.id)
+			.bind(limit)
+			.map(|row| self.query_to_item(row))
+			.fetch_all(&self.state.db)
+			.await?
+			.into_iter()
+			.collect();
+
+		Ok(Items::BaseBox(items?))
+	}
+}
@@ -16,6 +16,8 @@
 use activitystreams::activity::properties::ActorAndObjectProperties;
 use activitystreams::primitives::XsdAnyUri;
 use activitystreams::{object::properties::ObjectProperties, BaseBox};
+use serde::Serialize;
+use serde_json::Value as JsonValue;
 
 pub fn get_name<T>(obj: &T) -> Option<&str>
 where
@@ -47,6 +49,108 @@ where
 	Some(url)
 }
 
+pub fn get_in_reply_to<T>(obj: &T) -> Option<&XsdAnyUri>
+where
+	T: AsRef<ObjectProperties>,
+{
+	let object_props = obj.as_ref();
+	let in_reply_to = object_props.get_in_reply_to_xsd_any_uri()?;
+
+	Some(in_reply_to)
+}
+
+pub fn get_generator<T>(obj: &T) -> Option<&XsdAnyUri>
+where
+	T: AsRef<ObjectProperties>,
+{
+	let object_props = obj.as_ref();
+	let generator = object_props.get_generator_xsd_any_uri()?;
+
+	Some(generator)
+}
+
+/// The `name` of the first attachment, as used by Mastodon.
+pub fn get_alt_text<T>(obj: &T) -> Option<String>
+where
+	T: Serialize,
+{
+	let obj = serde_json::to_value(obj).ok()?;
+	let attachment = match obj.get("attachment")? {
+		JsonValue::Array(attachments) => attachments.first()?,
+		attachment => attachment,
+	};
+	let alt_text = attachment.get("name")?.as_str()?.trim();
+
+	if alt_text.is_empty() {
+		None
+	} else {
+		Some(alt_text.to_string())
+	}
+}
+
+/// Returns URLs and alt text of the images attached to an object. Of the
+/// URLs of an attachment the first one is used.
+pub fn get_attachments<T>(obj: &T) -> Vec<(XsdAnyUri, Option<String>)>
+where
+	T: Serialize,
+{
+	let obj = match serde_json::to_value(obj) {
+		Ok(obj) => obj,
+		Err(_) => return Vec::new(),
+	};
+	let attachments = match obj.get("attachment") {
+		Some(JsonValue::Array(attachments)) => attachments.iter().collect(),
+		Some(attachment) => vec![attachment],
+		None => Vec::new(),
+	};
+
+	attachments
+		.into_iter()
+		.filter_map(|attachment| {
+			let url = match attachment.get("url")? {
+				JsonValue::Array(urls) => urls.first()?,
+				url => url,
+			};
+			let url = match url {
+				JsonValue::String(url) => url.as_str(),
+				link => link.get("href")?.as_str()?,
+			};
+			let url = url.parse().ok()?;
+
+			let alt_text = attachment
+				.get("name")
+				.and_then(JsonValue::as_str)
+				.map(str::trim)
+				.filter(|alt_text| !alt_text.is_empty())
+				.map(str::to_string);
+
+			Some((url, alt_text))
+		})
+		.collect()
+}
+
+/// Without the `#`.
+pub fn get_hashtags<T>(obj: &T) -> Vec<String>
+where
+	T: Serialize,
+{
+	let obj = match serde_json::to_value(obj) {
+		Ok(obj) => obj,
+		Err(_) => return Vec::new(),
+	};
+	let tags = match obj.get("tag") {
+		Some(JsonValue::Array(tags)) => tags.iter().collect(),
+		Some(tag) => vec![tag],
+		None => Vec::new(),
+	};
+
+	tags.into_iter()
+		.filter(|tag| tag.get("type").and_then(JsonValue::as_str) == Some("Hashtag"))
+		.filter_map(|tag| tag.get("name")?.as_str())
+		.map(|name| name.strip_prefix('#').unwrap_or(name).to_string())
+		.collect()
+}
+
 pub fn get_to<T>(obj: &T) -> Option<Vec<&XsdAnyUri>>
 where
 	T: AsRef<ObjectProperties>,
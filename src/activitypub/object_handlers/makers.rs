@@ -16,7 +16,9 @@
 // TODO: Fix this issue.
 #![allow(clippy::unnecessary_unwrap)]
 
+use crate::content::Content;
 use crate::error::ApiError;
+use crate::mentions::Mention;
 use crate::url;
 use activitystreams::activity::properties::ActorAndObjectProperties;
 use activitystreams::activity::Create;
@@ -26,9 +28,96 @@ use activitystreams::object::Image;
 use activitystreams::primitives::XsdAnyUri;
 use activitystreams::BaseBox;
 use chrono::{DateTime, FixedOffset, Utc};
+use serde_json::{json, Value as JsonValue};
+use std::iter;
 use std::str::FromStr;
 use uuid::Uuid;
 
+#[derive(Clone, Debug)]
+pub struct ImageLink {
+	pub href: String,
+	pub media_type: String,
+	pub width: Option<i32>,
+	pub height: Option<i32>,
+}
+
+impl ImageLink {
+	fn to_json(&self) -> JsonValue {
+		let mut link = json!({
+			"type": "Link",
+			"href": self.href,
+			"mediaType": self.media_type,
+		});
+
+		if let (Some(width), Some(height)) = (self.width, self.height) {
+			link["width"] = json!(width);
+			link["height"] = json!(height);
+		}
+
+		link
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageMetadata {
+	pub link: ImageLink,
+	pub preview: Option<ImageLink>,
+	/// Listed in `url` after the original.
+	pub transcoded: Vec<ImageLink>,
+	pub blurhash: Option<String>,
+	/// In milliseconds.
+	pub duration_ms: Option<i64>,
+}
+
+impl ImageMetadata {
+	/// Animated images are `Document`s, as Mastodon publishes them.
+	fn object_type(&self) -> &'static str {
+		if self.link.media_type.starts_with("video/") {
+			"Video"
+		} else if self.duration_ms.is_some() {
+			"Document"
+		} else {
+			"Image"
+		}
+	}
+}
+
+/// Memes with several images, like comic strips, list them in `attachment`.
+#[derive(Clone, Debug)]
+pub struct ImageAttachment {
+	pub url: XsdAnyUri,
+	pub alt_text: Option<String>,
+	/// `None` if the image isn't stored on this instance.
+	pub metadata: Option<ImageMetadata>,
+}
+
+impl ImageAttachment {
+	fn to_json(&self) -> JsonValue {
+		let mut attachment = json!({
+			"type": "Image",
+			"url": self.url.as_str(),
+		});
+
+		if let Some(alt_text) = &self.alt_text {
+			attachment["name"] = json!(alt_text.trim());
+		}
+
+		if let Some(metadata) = &self.metadata {
+			attachment["type"] = json!(metadata.object_type());
+			attachment["mediaType"] = json!(metadata.link.media_type);
+			if let (Some(width), Some(height)) = (metadata.link.width, metadata.link.height) {
+				attachment["width"] = json!(width);
+				attachment["height"] = json!(height);
+			}
+			if let Some(blurhash) = &metadata.blurhash {
+				attachment["blurhash"] = json!(blurhash);
+			}
+		}
+
+		attachment
+	}
+}
+
 // TODO: Move common activity args into a separate struct and use that instead.
 #[allow(clippy::too_many_arguments)]
 pub fn new_image(
@@ -36,18 +125,25 @@ pub fn new_image(
 	actor_url: XsdAnyUri,
 	name: &str,
 	summary: Option<&str>,
-	image_url: XsdAnyUri,
+	content: Option<&Content>,
+	images: &[ImageAttachment],
+	tags: &[String],
+	mentions: &[Mention],
+	template_url: Option<XsdAnyUri>,
+	derived_from: Option<XsdAnyUri>,
 	published_at: DateTime<Utc>,
 	to: Option<Vec<XsdAnyUri>>,
 	cc: Option<Vec<XsdAnyUri>>,
-) -> Result<Image, ApiError> {
+) -> Result<BaseBox, ApiError> {
+	let first_image = images.first().ok_or(ApiError::OtherBadRequest)?;
+
 	let mut image = Image::new();
 	let object_props: &mut ObjectProperties = image.as_mut();
 
 	object_props.set_context_xsd_any_uri("https://www.w3.org/ns/activitystreams")?;
 	object_props.set_id(url::activitypub_object(activity_id))?;
 	object_props.set_name_xsd_string(name.trim())?;
-	object_props.set_url_xsd_any_uri(image_url)?;
+	object_props.set_url_xsd_any_uri(first_image.url.clone())?;
 	object_props.set_attributed_to_xsd_any_uri(actor_url)?;
 	object_props.set_published(DateTime::<FixedOffset>::from(published_at))?;
 
@@ -55,6 +151,21 @@ pub fn new_image(
 		object_props.set_summary_xsd_string(summary.trim())?;
 	}
 
+	if let Some(content) = content {
+		object_props.set_content_xsd_string(content.html.as_str())?;
+	}
+
+	// The template the meme was made from.
+	if let Some(template_url) = template_url {
+		object_props.set_generator_xsd_any_uri(template_url)?;
+	}
+
+	// The meme this one is a remix of. An `Image` in reply to another one is
+	// a remix of it, which other servers show in the original's thread.
+	if let Some(derived_from) = derived_from {
+		object_props.set_in_reply_to_xsd_any_uri(derived_from)?;
+	}
+
 	if to.is_none() && cc.is_none() {
 		return Err(ApiError::OtherBadRequest);
 	} else if to.is_some() && cc.is_none() {
@@ -83,7 +194,122 @@ pub fn new_image(
 		object_props.set_many_cc_xsd_any_uris(cc)?;
 	}
 
-	Ok(image)
+	let is_gallery = images.len() > 1;
+	if !is_gallery
+		&& first_image.metadata.is_none()
+		&& first_image.alt_text.is_none()
+		&& tags.is_empty()
+		&& mentions.is_empty()
+		&& content.is_none()
+	{
+		return Ok(BaseBox::try_from(image)?);
+	}
+
+	// activitystreams doesn't support `Link`s with dimensions in `url`, so
+	// they are added to the serialized object instead.
+	let mut image = serde_json::to_value(image)?;
+	let image_map = image.as_object_mut().ok_or(ApiError::InternalServerError)?;
+	image_map.insert(
+		"@context".to_string(),
+		json!([
+			"https://www.w3.org/ns/activitystreams",
+			{
+				"toot": "http://joinmastodon.org/ns#",
+				"blurhash": "toot:blurhash",
+				"Hashtag": "as:Hashtag",
+			},
+		]),
+	);
+
+	// Hashtags aren't a part of AS2 core, so activitystreams doesn't have
+	// them. Mentions are listed in the same `tag`.
+	if !tags.is_empty() || !mentions.is_empty() {
+		let tags: Vec<JsonValue> = tags
+			.iter()
+			.map(|tag| {
+				json!({
+					"type": "Hashtag",
+					"href": url::tag(tag),
+					"name": format!("#{}", tag),
+				})
+			})
+			.chain(mentions.iter().map(|mention| {
+				json!({
+					"type": "Mention",
+					"href": mention.actor_url.as_str(),
+					"name": mention.name(),
+				})
+			}))
+			.collect();
+		image_map.insert("tag".to_string(), json!(tags));
+	}
+
+	// The caption as written, so that it can be edited without converting the
+	// HTML back.
+	if let Some(content) = content {
+		image_map.insert(
+			"source".to_string(),
+			json!({
+				"content": content.source,
+				"mediaType": content.media_type,
+			}),
+		);
+	}
+
+	// All images are listed in `attachment`, with their alt text as `name`,
+	// which is where Mastodon, Pixelfed and compatible servers look for them.
+	// The object itself describes the first image.
+	if is_gallery || first_image.alt_text.is_some() {
+		image_map.insert(
+			"attachment".to_string(),
+			images.iter().map(ImageAttachment::to_json).collect(),
+		);
+	}
+
+	let metadata = match &first_image.metadata {
+		Some(metadata) => metadata,
+		None => return Ok(serde_json::from_value(image)?),
+	};
+
+	let url = if metadata.transcoded.is_empty() {
+		metadata.link.to_json()
+	} else {
+		iter::once(&metadata.link)
+			.chain(&metadata.transcoded)
+			.map(ImageLink::to_json)
+			.collect()
+	};
+	image_map.insert("url".to_string(), url);
+	image_map.insert("type".to_string(), json!(metadata.object_type()));
+	image_map.insert("mediaType".to_string(), json!(metadata.link.media_type));
+
+	if let Some(duration_ms) = metadata.duration_ms {
+		image_map.insert(
+			"duration".to_string(),
+			json!(format!(
+				"PT{}.{:03}S",
+				duration_ms / 1000,
+				duration_ms % 1000
+			)),
+		);
+	}
+
+	if let Some(preview) = &metadata.preview {
+		image_map.insert(
+			"icon".to_string(),
+			json!({
+				"type": "Image",
+				"mediaType": preview.media_type,
+				"url": preview.to_json(),
+			}),
+		);
+	}
+
+	if let Some(blurhash) = &metadata.blurhash {
+		image_map.insert("blurhash".to_string(), json!(blurhash));
+	}
+
+	Ok(serde_json::from_value(image)?)
 }
 
 pub fn new_create(
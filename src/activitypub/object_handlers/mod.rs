@@ -18,7 +18,8 @@ pub mod makers;
 pub mod utils;
 
 pub use getters::{
-	get_actor_xsd_any_uri, get_cc, get_name, get_object_base_box, get_object_xsd_any_uri,
-	get_summary, get_to, get_url,
+	get_actor_xsd_any_uri, get_alt_text, get_attachments, get_cc, get_generator, get_hashtags,
+	get_in_reply_to, get_name, get_object_base_box, get_object_xsd_any_uri, get_summary, get_to,
+	get_url,
 };
-pub use makers::{new_create, new_follow, new_image};
+pub use makers::{new_create, new_follow, new_image, ImageAttachment, ImageLink, ImageMetadata};
@@ -13,12 +13,21 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::activitypub::object_handlers::{self, ImageAttachment};
+use crate::audience::{self, Audience, Viewer, AUDIENCE_COLUMNS};
+use crate::audience_lists;
 use crate::error::ApiError;
+use crate::media;
+use crate::mentions::{self, Mention};
 use crate::state::AppState;
+use crate::tags;
 use crate::{routines, url as crate_url};
+use activitystreams::object::Image;
 use activitystreams::primitives::XsdAnyUri;
 use actix_web::web;
 use futures::future;
+use serde::Serialize;
+use serde_json::json;
 use sqlx::Row;
 use std::collections::HashSet;
 use std::str::FromStr;
@@ -26,14 +35,55 @@ use tracing::instrument;
 use url::Url;
 use uuid::Uuid;
 
-/// Maximum number of `to` and `cc` mentions that aren't public addressing.
-pub const MAX_NON_PUBLIC_CC_AND_TO: usize = 5;
+const PUBLIC_URI: &str = "https://www.w3.org/ns/activitystreams#Public";
+pub const MAX_IMAGES: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct PreparedImages {
+	pub attachments: Vec<ImageAttachment>,
+	pub media_ids: Vec<Uuid>,
+	/// Of the first image, if it's stored on this instance.
+	pub phash: Option<i64>,
+	/// Of the first image, if it's hosted elsewhere.
+	pub proxied_media_id: Option<Uuid>,
+}
+
+/// After the addressing policy of the instance was applied.
+#[derive(Clone, Debug, Default)]
+pub struct Addressing {
+	pub to: Vec<XsdAnyUri>,
+	pub cc: Vec<XsdAnyUri>,
+	pub rejected: Vec<RejectedRecipient>,
+}
+
+impl Addressing {
+	pub fn contains(&self, uri: &str) -> bool {
+		self.to.iter().chain(&self.cc).any(|to| to.as_str() == uri)
+	}
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RejectedRecipient {
+	pub id: String,
+	pub reason: RejectionReason,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RejectionReason {
+	TooManyRecipients,
+	RemoteRecipientsNotAllowed,
+	/// The audience list doesn't exist or belongs to someone else.
+	UnknownAudienceList,
+}
 
 #[derive(Clone, Debug, Default)]
 pub struct ToCcUuids {
 	pub mentions: Vec<Uuid>,
 	pub followers_of: Vec<Uuid>,
+	pub audience_lists: Vec<Uuid>,
 	pub has_public_uri: bool,
+	pub has_local_uri: bool,
 }
 
 impl From<ToCcUuidsRemoteAware> for ToCcUuids {
@@ -49,7 +99,9 @@ impl From<ToCcUuidsRemoteAware> for ToCcUuids {
 		Self {
 			mentions,
 			followers_of,
+			audience_lists: val.audience_lists,
 			has_public_uri: val.has_public_uri,
+			has_local_uri: val.has_local_uri,
 		}
 	}
 }
@@ -58,7 +110,9 @@ impl From<ToCcUuidsRemoteAware> for ToCcUuids {
 pub struct ToCcUuidsRemoteAware {
 	pub mentions: Vec<RemoteOrLocalId>,
 	pub followers_of: Vec<RemoteOrLocalId>,
+	pub audience_lists: Vec<Uuid>,
 	pub has_public_uri: bool,
+	pub has_local_uri: bool,
 }
 
 #[derive(Clone, Debug)]
@@ -85,15 +139,26 @@ where
 
 	let mut uuids = ToCcUuidsRemoteAware::default();
 	let mut futures = Vec::with_capacity(urls.size_hint().0);
+	let local_audience = crate_url::local_audience();
 
 	for url in urls {
 		let url = url.as_str();
 
-		if url == "https://www.w3.org/ns/activitystreams#Public" {
+		if url == PUBLIC_URI {
 			uuids.has_public_uri = true;
 			continue;
 		}
 
+		if url == local_audience {
+			uuids.has_local_uri = true;
+			continue;
+		}
+
+		if let Some(id) = audience_lists::id_from_url(url) {
+			uuids.audience_lists.push(id);
+			continue;
+		}
+
 		futures.push(actor_url_to_uuid(state.clone(), url));
 	}
 
@@ -111,7 +176,7 @@ where
 
 #[instrument(skip(state))]
 async fn actor_url_to_uuid(state: web::Data<AppState>, url: &str) -> Result<ToCcUuid, ApiError> {
-	if url == "https://www.w3.org/ns/activitystreams#Public" {
+	if url == PUBLIC_URI {
 		return Err(ApiError::InternalServerError);
 	}
 
@@ -168,58 +233,320 @@ async fn actor_url_to_uuid(state: web::Data<AppState>, url: &str) -> Result<ToCc
 	)))
 }
 
-pub fn limit_to_and_cc<'a, I>(iter: I) -> Result<Vec<XsdAnyUri>, ApiError>
+/// Accepts actor URLs and `acct:` URIs, but no collections.
+#[instrument(skip(state))]
+pub async fn resolve_accounts(
+	state: &web::Data<AppState>,
+	accounts: &[String],
+) -> Result<Vec<RemoteOrLocalId>, ApiError> {
+	let uris = accounts
+		.iter()
+		.map(|account| XsdAnyUri::from_str(account))
+		.collect::<Result<Vec<_>, _>>()
+		.map_err(|_| ApiError::OtherBadRequest)?;
+	let uris = resolve_handles(state, &uris).await?;
+	let uuids = actor_urls_to_uuids(state.clone(), &uris).await?;
+
+	if uuids.has_public_uri
+		|| uuids.has_local_uri
+		|| !uuids.followers_of.is_empty()
+		|| !uuids.audience_lists.is_empty()
+	{
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	Ok(uuids.mentions)
+}
+
+/// Other URIs are returned as is.
+#[instrument(skip(state, uris))]
+pub async fn resolve_handles<'a, I>(state: &AppState, uris: I) -> Result<Vec<XsdAnyUri>, ApiError>
 where
 	I: IntoIterator<Item = &'a XsdAnyUri>,
 {
-	let v: Vec<&XsdAnyUri> = iter.into_iter().collect();
-	let public_addressing = XsdAnyUri::from_str("https://www.w3.org/ns/activitystreams#Public")?;
+	let mut resolved = Vec::new();
+
+	for uri in uris {
+		if uri.as_str().starts_with("acct:") {
+			let handle = routines::Handle::parse(uri.as_str()).ok_or(ApiError::OtherBadRequest)?;
+			let actor_url = routines::resolve_handle(state, &handle).await?;
+
+			resolved.push(XsdAnyUri::try_from(actor_url.to_string())?);
+		} else {
+			resolved.push(uri.clone());
+		}
+	}
+
+	Ok(resolved)
+}
+
+/// Memes on other servers can't be checked, so any HTTPS URL is accepted for
+/// them.
+#[instrument(skip(state))]
+pub async fn check_derived_from(
+	state: &AppState,
+	user_id: Uuid,
+	uri: &XsdAnyUri,
+) -> Result<(), ApiError> {
+	let prefix = format!("{}/activities/", crate_url::shared_url());
+	let local_id = match uri.as_str().strip_prefix(&prefix) {
+		Some(rest) => rest,
+		None => {
+			let url = Url::parse(uri.as_str())?;
+			if url.scheme() != "https" {
+				return Err(ApiError::BadUrl);
+			}
+
+			return Ok(());
+		}
+	};
+
+	let activity_id = local_id
+		.strip_suffix("/object")
+		.and_then(|id| Uuid::parse_str(id).ok())
+		.ok_or(ApiError::OtherBadRequest)?;
+
+	let row = sqlx::query(&format!(
+		"SELECT {} FROM activities WHERE id = $1 AND this_instance = TRUE",
+		AUDIENCE_COLUMNS
+	))
+	.bind(activity_id)
+	.fetch_optional(&state.db)
+	.await?
+	.ok_or(ApiError::OtherBadRequest)?;
+
+	let audience = Audience::from_row(&row, 0);
+	if audience::can_view(state, Viewer::Local(user_id), &audience).await? {
+		Ok(())
+	} else {
+		Err(ApiError::Forbidden)
+	}
+}
+
+/// Alt text given at upload is used if the object doesn't have any.
+#[instrument(skip(state, obj))]
+pub async fn prepare_images(
+	state: &AppState,
+	user_id: Uuid,
+	obj: &Image,
+) -> Result<PreparedImages, ApiError> {
+	let mut images = object_handlers::get_attachments(obj);
+	if images.is_empty() {
+		let url = object_handlers::get_url(obj).ok_or(ApiError::OtherBadRequest)?;
+		images.push((url.clone(), object_handlers::get_alt_text(obj)));
+	}
+	if images.len() > MAX_IMAGES {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	let mut prepared = PreparedImages {
+		attachments: Vec::with_capacity(images.len()),
+		media_ids: Vec::new(),
+		phash: None,
+		proxied_media_id: None,
+	};
+	for (i, (url, alt_text)) in images.into_iter().enumerate() {
+		let image_media = media::find_by_url(state, url.as_str()).await?;
+		let metadata = match &image_media {
+			Some(image_media) => Some(media::image_metadata(state, image_media).await?),
+			None => None,
+		};
+
+		let alt_text = match (alt_text, &image_media) {
+			(Some(alt_text), _) => Some(alt_text),
+			(None, Some(image_media)) => {
+				media::get_alt_text(state, user_id, image_media.id).await?
+			}
+			(None, None) => None,
+		};
+		if state.require_alt_text && alt_text.is_none() {
+			return Err(ApiError::AltTextRequired);
+		}
+
+		let proxied_media_id = match &image_media {
+			Some(_) => None,
+			None => media::proxy::register(state, url.as_str()).await?,
+		};
+		let url = match proxied_media_id {
+			Some(id) => XsdAnyUri::try_from(crate_url::media_proxy(id))?,
+			None => url,
+		};
+
+		if let Some(image_media) = &image_media {
+			prepared.media_ids.push(image_media.id);
+		}
+
+		// Reposts are found by the first image.
+		if i == 0 {
+			prepared.phash = image_media.and_then(|image_media| image_media.phash);
+			prepared.proxied_media_id = proxied_media_id;
+		}
+
+		prepared.attachments.push(ImageAttachment {
+			url,
+			alt_text,
+			metadata,
+		});
+	}
 
-	let contains_public_addressing_before_take = v.contains(&&public_addressing);
-	let mut v: Vec<XsdAnyUri> = v
+	Ok(prepared)
+}
+
+/// Hashtags in the text come first, followed by those only in `tag`.
+pub fn collect_tags(obj: &Image) -> Vec<String> {
+	let texts = object_handlers::get_name(obj)
 		.into_iter()
-		.take(MAX_NON_PUBLIC_CC_AND_TO)
-		.cloned()
-		.collect();
-	let contains_public_addressing_after_take = v.contains(&public_addressing);
+		.chain(object_handlers::get_summary(obj));
+	let mut tags = tags::parse(texts);
+
+	for tag in object_handlers::get_hashtags(obj) {
+		if let Some(tag) = tags::normalize(&tag) {
+			if !tags.contains(&tag) {
+				tags.push(tag);
+			}
+		}
+	}
 
-	if contains_public_addressing_before_take && !contains_public_addressing_after_take {
-		v.push(public_addressing);
+	tags.truncate(tags::MAX_TAGS);
+	tags
+}
+
+pub async fn collect_mentions(state: &AppState, obj: &Image) -> Vec<Mention> {
+	let texts = object_handlers::get_name(obj)
+		.into_iter()
+		.chain(object_handlers::get_summary(obj));
+	let mut handles = Vec::new();
+	for handle in texts.flat_map(|text| mentions::parse(text, &state.domain)) {
+		if !handles.contains(&handle) {
+			handles.push(handle);
+		}
 	}
 
-	Ok(v)
+	mentions::resolve(state, &handles).await
 }
 
-pub fn merge_and_limit_mentions<'a, T>(
+pub fn add_mentions(
+	to: &[XsdAnyUri],
+	cc: &mut Vec<XsdAnyUri>,
+	mentions: &[Mention],
+) -> Result<(), ApiError> {
+	for mention in mentions {
+		let uri = XsdAnyUri::try_from(mention.actor_url.to_string())?;
+		if !to.contains(&uri) && !cc.contains(&uri) {
+			cc.push(uri);
+		}
+	}
+
+	Ok(())
+}
+
+pub fn merge_mentions<'a, T>(
 	object_to: T,
 	object_cc: T,
 	activity_to: T,
 	activity_cc: T,
-) -> Result<(Vec<XsdAnyUri>, Vec<XsdAnyUri>), ApiError>
+) -> (Vec<&'a XsdAnyUri>, Vec<&'a XsdAnyUri>)
 where
 	T: IntoIterator<Item = &'a XsdAnyUri>,
 {
-	let mut to_deduplicated = HashSet::new();
-	let mut cc_deduplicated = HashSet::new();
+	let mut to = Vec::new();
+	let mut cc = Vec::new();
 
-	for uri in object_to {
-		to_deduplicated.insert(uri);
+	for uri in object_to.into_iter().chain(activity_to) {
+		if !to.contains(&uri) {
+			to.push(uri);
+		}
 	}
 
-	for uri in object_cc {
-		cc_deduplicated.insert(uri);
+	for uri in object_cc.into_iter().chain(activity_cc) {
+		if !to.contains(&uri) && !cc.contains(&uri) {
+			cc.push(uri);
+		}
 	}
 
-	for uri in activity_to {
-		to_deduplicated.insert(uri);
-	}
+	(to, cc)
+}
+
+/// The public and the local audience don't count towards the limit, and a
+/// list counts as all of its members.
+#[instrument(skip(state, to, cc))]
+pub async fn apply_addressing_policy(
+	state: &AppState,
+	user_id: Uuid,
+	to: &[XsdAnyUri],
+	cc: &[XsdAnyUri],
+) -> Result<Addressing, ApiError> {
+	let policy = &state.addressing_policy;
+	let local_audience = crate_url::local_audience();
+	let local_prefix = format!("{}/", crate_url::shared_url());
+
+	let list_ids: Vec<Uuid> = to
+		.iter()
+		.chain(cc)
+		.filter_map(|uri| audience_lists::id_from_url(uri.as_str()))
+		.collect();
+	let list_sizes = audience_lists::member_counts(state, user_id, &list_ids).await?;
+
+	let mut addressing = Addressing::default();
+	let mut seen = HashSet::new();
+	let mut num_of_recipients = 0;
+
+	let uris = to
+		.iter()
+		.map(|uri| (uri, true))
+		.chain(cc.iter().map(|uri| (uri, false)));
+	for (uri, is_to) in uris {
+		let url = uri.as_str();
+		if !seen.insert(url) {
+			continue;
+		}
 
-	for uri in activity_cc {
-		cc_deduplicated.insert(uri);
+		let rejection = if url == PUBLIC_URI || url == local_audience {
+			None
+		} else if let Some(id) = audience_lists::id_from_url(url) {
+			match list_sizes.get(&id) {
+				Some(size) if num_of_recipients + size > policy.max_recipients => {
+					Some(RejectionReason::TooManyRecipients)
+				}
+				Some(size) => {
+					num_of_recipients += size;
+					None
+				}
+				None => Some(RejectionReason::UnknownAudienceList),
+			}
+		} else if !policy.allow_remote_recipients && !url.starts_with(&local_prefix) {
+			Some(RejectionReason::RemoteRecipientsNotAllowed)
+		} else if num_of_recipients >= policy.max_recipients {
+			Some(RejectionReason::TooManyRecipients)
+		} else {
+			num_of_recipients += 1;
+			None
+		};
+
+		match rejection {
+			Some(reason) => addressing.rejected.push(RejectedRecipient {
+				id: url.to_string(),
+				reason,
+			}),
+			None if is_to => addressing.to.push(uri.clone()),
+			None => addressing.cc.push(uri.clone()),
+		}
 	}
 
-	let to = limit_to_and_cc(to_deduplicated.into_iter())?;
-	let cc = limit_to_and_cc(cc_deduplicated.into_iter())?;
+	Ok(addressing)
+}
+
+/// `Video`s and `Document`s are handled like `Image`s.
+pub fn into_image<T>(obj: &T) -> Option<Image>
+where
+	T: Serialize,
+{
+	let mut obj = serde_json::to_value(obj).ok()?;
+	match obj.get("type")?.as_str()? {
+		"Image" | "Video" | "Document" => {}
+		_ => return None,
+	}
 
-	Ok((to, cc))
+	obj["type"] = json!("Image");
+	serde_json::from_value(obj).ok()
 }
@@ -15,6 +15,7 @@
 
 use crate::activitypub::object_handlers;
 use crate::error::ApiError;
+use crate::feed;
 use crate::state::AppState;
 use crate::url;
 use activitystreams::activity::Follow;
@@ -91,6 +92,8 @@ pub async fn post_follow(
 
 		tx.commit().await?;
 
+		feed::add_followed(&state, subject_user_id, object_user_id).await?;
+
 		Ok(HttpResponse::Created()
 			.insert_header((header::LOCATION, url::activitypub_activity(activity_id)))
 			.finish())
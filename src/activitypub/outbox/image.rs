@@ -13,26 +13,19 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-// TODO: Fix this issue.
-#![allow(clippy::unnecessary_unwrap)]
-
+use super::publish::{self, Post};
 use crate::activitypub::object_handlers;
-use crate::activitypub::object_handlers::utils::{
-	self, RemoteOrLocalId, ToCcUuids, ToCcUuidsRemoteAware,
-};
+use crate::activitypub::object_handlers::utils::{self, Addressing};
+use crate::content::Content;
 use crate::error::ApiError;
+use crate::media::templates;
 use crate::state::AppState;
-use crate::{routines, url};
+use crate::url;
 use activitystreams::object::Image;
 use activitystreams::primitives::XsdAnyUri;
-use actix_web::http::header;
 use actix_web::web;
 use actix_web::HttpResponse;
 use chrono::Utc;
-use rsa::pkcs8::DecodePrivateKey;
-use rsa::RsaPrivateKey;
-use sqlx::Row;
-use std::collections::HashSet;
 use tracing::instrument;
 use uuid::Uuid;
 
@@ -44,35 +37,78 @@ pub async fn post_image(
 	username: &str,
 ) -> Result<HttpResponse, ApiError> {
 	let activity_id = Uuid::new_v4();
-	let activity_url = url::activitypub_activity(activity_id);
 	let actor_url = XsdAnyUri::try_from(url::activitypub_actor(username))?;
 
 	let name = object_handlers::get_name(&body).ok_or(ApiError::OtherBadRequest)?;
 	let summary = object_handlers::get_summary(&body);
-	let image_url = object_handlers::get_url(&body).ok_or(ApiError::OtherBadRequest)?;
+	let derived_from = object_handlers::get_in_reply_to(&body).cloned();
+	if let Some(derived_from) = &derived_from {
+		utils::check_derived_from(&state, user_id, derived_from).await?;
+	}
+	let template_url = match object_handlers::get_generator(&body) {
+		Some(generator) => templates::find_by_url(&state, generator.as_str())
+			.await?
+			.map(|template| XsdAnyUri::try_from(url::template(template.id)))
+			.transpose()?,
+		None => None,
+	};
+	let images = utils::prepare_images(&state, user_id, &body).await?;
+	let tags = utils::collect_tags(&body);
+	let mut mentions = utils::collect_mentions(&state, &body).await;
 
 	let to = object_handlers::get_to(&body);
 	let cc = object_handlers::get_cc(&body);
 
 	let to = if let Some(to) = to {
-		Some(utils::limit_to_and_cc(to.into_iter())?)
+		Some(utils::resolve_handles(&state, to).await?)
 	} else {
 		None
 	};
 
-	let cc = if let Some(cc) = cc {
-		Some(utils::limit_to_and_cc(cc.into_iter())?)
+	let mut cc = if let Some(cc) = cc {
+		Some(utils::resolve_handles(&state, cc).await?)
 	} else {
 		None
 	};
 
+	if !mentions.is_empty() {
+		let to = to.as_deref().unwrap_or_default();
+		utils::add_mentions(to, cc.get_or_insert_with(Vec::new), &mentions)?;
+	}
+
+	let addressing = utils::apply_addressing_policy(
+		&state,
+		user_id,
+		to.as_deref().unwrap_or_default(),
+		cc.as_deref().unwrap_or_default(),
+	)
+	.await?;
+
+	// Mentions of accounts the addressing policy rejected are left as plain
+	// text.
+	mentions.retain(|mention| addressing.contains(mention.actor_url.as_str()));
+	let content = summary.map(|summary| Content::from_markdown(summary, &state.domain, &mentions));
+
+	let Addressing {
+		to: allowed_to,
+		cc: allowed_cc,
+		rejected,
+	} = addressing;
+	let to = to.map(|_| allowed_to);
+	let cc = cc.map(|_| allowed_cc);
+
 	let published_at = Utc::now();
 	let new_image = object_handlers::new_image(
 		activity_id,
 		actor_url.clone(),
 		name,
 		summary,
-		image_url.clone(),
+		content.as_ref(),
+		&images.attachments,
+		&tags,
+		&mentions,
+		template_url,
+		derived_from.clone(),
 		published_at,
 		to.clone(),
 		cc.clone(),
@@ -82,7 +118,7 @@ pub async fn post_image(
 		activity_id,
 		actor_url,
 		published_at,
-		new_image.try_into()?,
+		new_image,
 		to.clone(),
 		cc.clone(),
 	)?;
@@ -99,77 +135,24 @@ pub async fn post_image(
 		None
 	};
 
-	let (to, cc, is_public) = if to.is_some() && cc.is_some() {
-		let to = to.unwrap();
-		let cc = cc.unwrap();
-
-		let to_has_public_uri = to.has_public_uri;
-		let cc_has_public_uri = cc.has_public_uri;
-
-		(to, cc, to_has_public_uri || cc_has_public_uri)
-	} else if to.is_some() && cc.is_none() {
-		let to = to.unwrap();
-		let has_public_uri = to.has_public_uri;
-
-		(to, ToCcUuidsRemoteAware::default(), has_public_uri)
-	} else if to.is_none() && cc.is_some() {
-		let cc = cc.unwrap();
-		let has_public_uri = cc.has_public_uri;
-
-		(ToCcUuidsRemoteAware::default(), cc, has_public_uri)
-	} else {
-		unreachable!();
-	};
-
-	let mut deliver_to = HashSet::new();
-	for id in &to.mentions {
-		if let RemoteOrLocalId::Remote(_, url) = id {
-			deliver_to.insert(url.clone());
-		}
-	}
-
-	for id in &cc.mentions {
-		if let RemoteOrLocalId::Remote(_, url) = id {
-			deliver_to.insert(url.clone());
-		}
-	}
-
-	let to: ToCcUuids = to.into();
-	let cc: ToCcUuids = cc.into();
-
-	let serialized_activity = serde_json::to_value(activity)?;
-	sqlx::query("INSERT INTO activities (id, user_id, this_instance, published_at, activity, is_public, to_mentions, cc_mentions, to_followers_of, cc_followers_of) VALUES ($1, $2, TRUE, $3, $4, $5, $6, $7, $8, $9)")
-		.bind(activity_id)
-		.bind(user_id)
-		.bind(published_at)
-		.bind(&serialized_activity)
-		.bind(is_public)
-		.bind(to.mentions)
-		.bind(cc.mentions)
-		.bind(to.followers_of)
-		.bind(cc.followers_of)
-		.execute(&state.db)
-		.await?;
-
-	if !deliver_to.is_empty() {
-		let private_key_pem: String = sqlx::query("SELECT private_key FROM users WHERE id = $1")
-			.bind(user_id)
-			.fetch_one(&state.db)
-			.await?
-			.get(0);
-
-		let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem)?;
-
-		actix_web::rt::spawn(routines::deliver_activity(
-			state.clone(),
-			serialized_activity,
-			deliver_to,
-			url::activitypub_actor(username),
-			private_key,
-		));
+	// A meme has to be addressed to someone.
+	if to.is_none() && cc.is_none() {
+		return Err(ApiError::OtherBadRequest);
 	}
 
-	Ok(HttpResponse::Created()
-		.insert_header((header::LOCATION, activity_url))
-		.finish())
+	let post = Post {
+		activity_id,
+		published_at,
+		activity,
+		to: to.unwrap_or_default(),
+		cc: cc.unwrap_or_default(),
+		media_ids: images.media_ids,
+		image_phash: images.phash,
+		proxied_media_id: images.proxied_media_id,
+		derived_from,
+		tags,
+		mentions,
+		rejected,
+	};
+	publish::publish(state, user_id, username, post).await
 }
@@ -16,16 +16,20 @@
 mod create;
 mod follow;
 mod image;
+mod publish;
 
+use crate::activitypub::object_handlers::utils::{self, RejectedRecipient};
 use crate::error::ApiError;
 use crate::state::AppState;
 use activitystreams::activity::kind::{
 	AcceptType, CreateType, DeleteType, FollowType, LikeType, RemoveType, UpdateType,
 };
 use activitystreams::activity::{Create, Follow};
-use activitystreams::object::kind::{ImageType, NoteType};
-use activitystreams::object::{Image, ObjectBox};
+use activitystreams::object::kind::{DocumentType, ImageType, NoteType, VideoType};
+use activitystreams::object::ObjectBox;
+use actix_web::http::header;
 use actix_web::{web, HttpResponse};
+use serde_json::json;
 use tracing::instrument;
 use uuid::Uuid;
 
@@ -52,8 +56,11 @@ pub async fn post_to_outbox(
 		body if body.is_kind(RemoveType) => todo!("RemoveType"),
 		body if body.is_kind(UpdateType) => todo!("UpdateType"),
 		// Non-activity objects
-		body if body.is_kind(ImageType) => {
-			let body: Image = body.to_owned().into_concrete().unwrap();
+		body if body.is_kind(ImageType)
+			|| body.is_kind(VideoType)
+			|| body.is_kind(DocumentType) =>
+		{
+			let body = utils::into_image(&*body).ok_or(ApiError::OtherBadRequest)?;
 			image::post_image(state, body, user_id, username).await?
 		}
 		body if body.is_kind(NoteType) => todo!("NoteType"),
@@ -61,3 +68,15 @@ pub async fn post_to_outbox(
 		_ => todo!("Other"),
 	})
 }
+
+/// Lists recipients the addressing policy left out, if any.
+fn created(activity_url: String, rejected: Vec<RejectedRecipient>) -> HttpResponse {
+	let mut response = HttpResponse::Created();
+	response.insert_header((header::LOCATION, activity_url));
+
+	if rejected.is_empty() {
+		response.finish()
+	} else {
+		response.json(json!({ "rejectedRecipients": rejected }))
+	}
+}
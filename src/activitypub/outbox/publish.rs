@@ -0,0 +1,172 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::activitypub::object_handlers::utils::{
+	RejectedRecipient, RemoteOrLocalId, ToCcUuids, ToCcUuidsRemoteAware,
+};
+use crate::audience_lists;
+use crate::content;
+use crate::error::ApiError;
+use crate::feed;
+use crate::media;
+use crate::mentions::{self, Mention};
+use crate::notifications;
+use crate::state::AppState;
+use crate::tags;
+use crate::{routines, url};
+use activitystreams::activity::Create;
+use activitystreams::primitives::XsdAnyUri;
+use actix_web::{web, HttpResponse};
+use chrono::{DateTime, Utc};
+use rsa::pkcs8::DecodePrivateKey;
+use rsa::RsaPrivateKey;
+use sqlx::Row;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+/// Recipients are already resolved.
+pub struct Post {
+	pub activity_id: Uuid,
+	pub published_at: DateTime<Utc>,
+	pub activity: Create,
+	pub to: ToCcUuidsRemoteAware,
+	pub cc: ToCcUuidsRemoteAware,
+	pub media_ids: Vec<Uuid>,
+	pub image_phash: Option<i64>,
+	pub proxied_media_id: Option<Uuid>,
+	pub derived_from: Option<XsdAnyUri>,
+	pub tags: Vec<String>,
+	pub mentions: Vec<Mention>,
+	pub rejected: Vec<RejectedRecipient>,
+}
+
+pub async fn publish(
+	state: web::Data<AppState>,
+	user_id: Uuid,
+	username: &str,
+	post: Post,
+) -> Result<HttpResponse, ApiError> {
+	let Post {
+		activity_id,
+		published_at,
+		activity,
+		to,
+		cc,
+		media_ids,
+		image_phash,
+		proxied_media_id,
+		derived_from,
+		tags,
+		mentions,
+		rejected,
+	} = post;
+
+	let mut deliver_to = HashSet::new();
+	for id in to.mentions.iter().chain(&cc.mentions) {
+		if let RemoteOrLocalId::Remote(_, url) = id {
+			deliver_to.insert(url.clone());
+		}
+	}
+
+	let to: ToCcUuids = to.into();
+	let cc: ToCcUuids = cc.into();
+
+	let is_public = to.has_public_uri || cc.has_public_uri;
+	let is_local_only = to.has_local_uri || cc.has_local_uri;
+	if is_local_only && is_public {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	let mut serialized_activity = serde_json::to_value(activity)?;
+	// Activities are stored and delivered only with allowed HTML.
+	content::sanitize_activity(&mut serialized_activity);
+	let list_ids: Vec<Uuid> = to
+		.audience_lists
+		.iter()
+		.chain(&cc.audience_lists)
+		.copied()
+		.collect();
+
+	let mut tx = state.db.begin().await?;
+	sqlx::query("INSERT INTO activities (id, user_id, this_instance, published_at, activity, is_public, is_local_only, to_mentions, cc_mentions, to_followers_of, cc_followers_of, audience_lists) VALUES ($1, $2, TRUE, $3, $4, $5, $6, $7, $8, $9, $10, $11)")
+		.bind(activity_id)
+		.bind(user_id)
+		.bind(published_at)
+		.bind(&serialized_activity)
+		.bind(is_public)
+		.bind(is_local_only)
+		.bind(to.mentions)
+		.bind(cc.mentions)
+		.bind(to.followers_of)
+		.bind(cc.followers_of)
+		.bind(&list_ids)
+		.execute(&mut tx)
+		.await?;
+
+	media::record_usage(&mut tx, activity_id, &media_ids).await?;
+	if let Some(hash) = image_phash {
+		media::record_image_hash(&mut tx, activity_id, hash).await?;
+	}
+
+	feed::fan_out(&mut tx, &[activity_id]).await?;
+	tags::record(&mut tx, activity_id, &tags).await?;
+	let usernames = mentions::local_usernames(&mentions, &state.domain);
+	notifications::record_mentions(&mut tx, activity_id, &usernames).await?;
+
+	if let Some(derived_from) = &derived_from {
+		sqlx::query("INSERT INTO remixes (activity_id, derived_from) VALUES ($1, $2)")
+			.bind(activity_id)
+			.bind(derived_from.as_str())
+			.execute(&mut tx)
+			.await?;
+	}
+
+	// Delivery and the proxy only start once everything is recorded.
+	tx.commit().await?;
+
+	if let Some(id) = proxied_media_id {
+		actix_web::rt::spawn(media::proxy::cache_and_hash(state.clone(), id, activity_id));
+	}
+
+	// Members of audience lists are looked up when the meme is delivered, so
+	// it goes to whoever is in the lists at that point.
+	if !is_local_only && state.addressing_policy.allow_remote_recipients {
+		deliver_to.extend(audience_lists::remote_members(&state, &list_ids).await?);
+	}
+
+	// Local-only memes never leave this instance.
+	if !is_local_only && !deliver_to.is_empty() {
+		let private_key_pem: String = sqlx::query("SELECT private_key FROM users WHERE id = $1")
+			.bind(user_id)
+			.fetch_one(&state.db)
+			.await?
+			.get(0);
+
+		let private_key = RsaPrivateKey::from_pkcs8_pem(&private_key_pem)?;
+
+		actix_web::rt::spawn(routines::deliver_activity(
+			state.clone(),
+			serialized_activity,
+			deliver_to,
+			url::activitypub_actor(username),
+			private_key,
+		));
+	}
+
+	Ok(super::created(
+		url::activitypub_activity(activity_id),
+		rejected,
+	))
+}
@@ -0,0 +1,162 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::account;
+use crate::error::ApiError;
+use crate::signatures;
+use crate::state::AppState;
+use actix_web::HttpRequest;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Matches [`can_view`]. `$2` is the ID of the viewer, NULL if anonymous.
+pub const VISIBLE_TO_VIEWER: &str = "
+	(
+		(
+			activities.is_local_only = FALSE
+			AND (
+				activities.is_public = TRUE
+				OR activities.user_id = $2
+				OR $2 = ANY(activities.to_mentions)
+				OR $2 = ANY(activities.cc_mentions)
+				OR activities.to_followers_of && ARRAY(SELECT object_user_id FROM follows WHERE subject_user_id = $2 AND pending = FALSE)
+				OR activities.cc_followers_of && ARRAY(SELECT object_user_id FROM follows WHERE subject_user_id = $2 AND pending = FALSE)
+				OR activities.audience_lists && ARRAY(SELECT list_id FROM audience_list_members WHERE member_user_id = $2)
+			)
+		)
+		OR (
+			activities.is_local_only = TRUE
+			AND $2 IN (SELECT id FROM users WHERE this_instance = TRUE)
+		)
+	)
+";
+
+/// In the order [`Audience::from_row`] reads them.
+pub const AUDIENCE_COLUMNS: &str = "activities.user_id, activities.is_public, activities.is_local_only, activities.to_mentions, activities.cc_mentions, activities.to_followers_of, activities.cc_followers_of, activities.audience_lists";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Viewer {
+	Anonymous,
+	Local(Uuid),
+	Remote(Uuid),
+}
+
+impl Viewer {
+	/// A session cookie takes precedence over an HTTP signature.
+	#[instrument(skip(state, req))]
+	pub async fn from_request(state: &AppState, req: &HttpRequest) -> Result<Self, ApiError> {
+		if let Some(username) = account::ensure_signed_in(state, req) {
+			let user_id: Option<Uuid> =
+				sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
+					.bind(username)
+					.fetch_optional(&state.db)
+					.await?
+					.map(|row| row.get(0));
+			if let Some(user_id) = user_id {
+				return Ok(Self::Local(user_id));
+			}
+		}
+
+		if let Some(user_id) = signatures::verify(state, req).await? {
+			return Ok(Self::Remote(user_id));
+		}
+
+		Ok(Self::Anonymous)
+	}
+
+	pub fn user_id(&self) -> Option<Uuid> {
+		match self {
+			Self::Anonymous => None,
+			Self::Local(user_id) | Self::Remote(user_id) => Some(*user_id),
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct Audience {
+	pub author_id: Uuid,
+	pub is_public: bool,
+	pub is_local_only: bool,
+	pub to_mentions: Vec<Uuid>,
+	pub cc_mentions: Vec<Uuid>,
+	pub to_followers_of: Vec<Uuid>,
+	pub cc_followers_of: Vec<Uuid>,
+	pub audience_lists: Vec<Uuid>,
+}
+
+impl Audience {
+	pub fn from_row(row: &PgRow, offset: usize) -> Self {
+		Self {
+			author_id: row.get(offset),
+			is_public: row.get(offset + 1),
+			is_local_only: row.get(offset + 2),
+			to_mentions: row.get(offset + 3),
+			cc_mentions: row.get(offset + 4),
+			to_followers_of: row.get(offset + 5),
+			cc_followers_of: row.get(offset + 6),
+			audience_lists: row.get(offset + 7),
+		}
+	}
+}
+
+/// Local-only activities are only visible to users of this instance.
+#[instrument(skip(state))]
+pub async fn can_view(
+	state: &AppState,
+	viewer: Viewer,
+	audience: &Audience,
+) -> Result<bool, ApiError> {
+	if audience.is_local_only {
+		return Ok(matches!(viewer, Viewer::Local(_)));
+	}
+
+	if audience.is_public {
+		return Ok(true);
+	}
+
+	let user_id = match viewer.user_id() {
+		Some(user_id) => user_id,
+		None => return Ok(false),
+	};
+
+	if audience.author_id == user_id
+		|| audience.to_mentions.contains(&user_id)
+		|| audience.cc_mentions.contains(&user_id)
+	{
+		return Ok(true);
+	}
+
+	let followers_of: Vec<Uuid> = audience
+		.to_followers_of
+		.iter()
+		.chain(&audience.cc_followers_of)
+		.copied()
+		.collect();
+	if followers_of.is_empty() && audience.audience_lists.is_empty() {
+		return Ok(false);
+	}
+
+	let is_in_audience: bool = sqlx::query("SELECT EXISTS (SELECT 1 FROM follows WHERE subject_user_id = $1 AND object_user_id = ANY($2) AND pending = FALSE) OR EXISTS (SELECT 1 FROM audience_list_members WHERE member_user_id = $1 AND list_id = ANY($3))")
+		.bind(user_id)
+		.bind(followers_of)
+		.bind(&audience.audience_lists)
+		.fetch_one(&state.db)
+		.await?
+		.get(0);
+
+	Ok(is_in_audience)
+}
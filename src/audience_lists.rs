@@ -0,0 +1,94 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use crate::feed;
+use crate::lists;
+use crate::state::AppState;
+use crate::url as crate_url;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use tracing::instrument;
+use url::Url;
+use uuid::Uuid;
+
+#[instrument(skip(state))]
+pub async fn set_members(state: &AppState, id: Uuid, members: &[Uuid]) -> Result<(), ApiError> {
+	if members.len() > lists::MAX_MEMBERS {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	let mut tx = state.db.begin().await?;
+
+	sqlx::query("DELETE FROM audience_list_members WHERE list_id = $1")
+		.bind(id)
+		.execute(&mut tx)
+		.await?;
+	sqlx::query("INSERT INTO audience_list_members (list_id, member_user_id) SELECT $1, unnest($2::uuid[]) ON CONFLICT DO NOTHING")
+		.bind(id)
+		.bind(members)
+		.execute(&mut tx)
+		.await?;
+	feed::refresh_audience_list(&mut tx, id).await?;
+
+	tx.commit().await?;
+	Ok(())
+}
+
+/// Returns actor URLs of members on other servers.
+#[instrument(skip(state))]
+pub async fn remote_members(state: &AppState, ids: &[Uuid]) -> Result<HashSet<Url>, ApiError> {
+	if ids.is_empty() {
+		return Ok(HashSet::new());
+	}
+
+	let urls: Vec<String> = sqlx::query("SELECT DISTINCT users.instance_url FROM audience_list_members, users WHERE audience_list_members.list_id = ANY($1) AND users.id = audience_list_members.member_user_id AND users.this_instance = FALSE")
+		.bind(ids)
+		.map(|row: PgRow| row.get(0))
+		.fetch_all(&state.db)
+		.await?;
+
+	Ok(urls.iter().filter_map(|url| Url::parse(url).ok()).collect())
+}
+
+#[instrument(skip(state))]
+pub async fn member_counts(
+	state: &AppState,
+	user_id: Uuid,
+	ids: &[Uuid],
+) -> Result<HashMap<Uuid, usize>, ApiError> {
+	if ids.is_empty() {
+		return Ok(HashMap::new());
+	}
+
+	let counts: Vec<(Uuid, i64)> = sqlx::query("SELECT id, (SELECT COUNT(*) FROM audience_list_members WHERE list_id = audience_lists.id) FROM audience_lists WHERE user_id = $1 AND id = ANY($2)")
+		.bind(user_id)
+		.bind(ids)
+		.map(|row: PgRow| (row.get(0), row.get(1)))
+		.fetch_all(&state.db)
+		.await?;
+
+	Ok(counts
+		.into_iter()
+		.map(|(id, count)| (id, count as usize))
+		.collect())
+}
+
+pub fn id_from_url(url: &str) -> Option<Uuid> {
+	let prefix = format!("{}/audiences/", crate_url::shared_url());
+	url.strip_prefix(&prefix)
+		.and_then(|id| Uuid::parse_str(id).ok())
+}
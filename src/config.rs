@@ -28,6 +28,22 @@ pub struct Config {
 	pub num_of_db_pool_connections: u32,
 	pub token_rsa_public_key_pem_filepath: String,
 	pub token_rsa_private_key_pem_filepath: String,
+	pub media_storage: MediaStorageConfig,
+	/// In bytes.
+	pub max_upload_size: usize,
+	/// In bytes.
+	pub max_video_upload_size: usize,
+	pub max_video_duration_secs: u64,
+	/// In bytes.
+	pub media_quota: u64,
+	pub require_alt_text: bool,
+	pub timelines_require_sign_in: bool,
+	pub addressing: AddressingPolicy,
+	/// In bytes.
+	pub remote_media_max_size: usize,
+	/// In bytes.
+	pub remote_media_cache_size: u64,
+	pub remote_media_cache_max_age_days: i32,
 }
 
 impl Config {
@@ -38,3 +54,28 @@ impl Config {
 		Ok(val)
 	}
 }
+
+/// Recipients over the limits are left out and reported back to the author.
+#[derive(Clone, Debug, Deserialize)]
+pub struct AddressingPolicy {
+	/// The public, the local audience and audience lists don't count.
+	pub max_recipients: usize,
+	pub allow_remote_recipients: bool,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MediaStorageConfig {
+	Local {
+		directory: String,
+	},
+	S3 {
+		endpoint: String,
+		region: String,
+		bucket: String,
+		access_key: String,
+		secret_key: String,
+		/// Defaults to `{endpoint}/{bucket}`.
+		public_url: Option<String>,
+	},
+}
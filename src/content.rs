@@ -0,0 +1,200 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::mentions::{self, Mention};
+use ammonia::{Builder, UrlRelative};
+use once_cell::sync::Lazy;
+use pulldown_cmark::{html, CowStr, Event, Options, Parser, Tag};
+use serde_json::Value as JsonValue;
+use std::collections::HashSet;
+
+pub const MARKDOWN: &str = "text/markdown";
+
+static SANITIZER: Lazy<Builder<'static>> = Lazy::new(|| {
+	let mut sanitizer = Builder::default();
+	sanitizer
+		.tags(HashSet::from([
+			"a",
+			"blockquote",
+			"br",
+			"code",
+			"del",
+			"em",
+			"li",
+			"ol",
+			"p",
+			"pre",
+			"span",
+			"strong",
+			"ul",
+		]))
+		.add_allowed_classes("a", &["mention", "u-url", "hashtag"])
+		.add_allowed_classes("span", &["h-card"])
+		.url_schemes(HashSet::from(["http", "https"]))
+		.url_relative(UrlRelative::Deny)
+		.link_rel(Some("nofollow noopener noreferrer"));
+
+	sanitizer
+});
+
+#[derive(Clone, Debug)]
+pub struct Content {
+	pub html: String,
+	pub source: String,
+	pub media_type: &'static str,
+}
+
+impl Content {
+	/// HTML in the source is shown as text.
+	pub fn from_markdown(source: &str, local_domain: &str, mentions: &[Mention]) -> Self {
+		let source = source.trim();
+
+		let mut events: Vec<Event> = Vec::new();
+		for event in Parser::new_ext(source, Options::ENABLE_STRIKETHROUGH) {
+			let event = match event {
+				Event::Html(html) => Event::Text(html),
+				// Line breaks in captions are meant to be shown.
+				Event::SoftBreak => Event::HardBreak,
+				event => event,
+			};
+
+			// The parser splits text at characters that could start formatting,
+			// so adjacent text is merged for mentions to be found in it.
+			match (events.last_mut(), event) {
+				(Some(Event::Text(last)), Event::Text(text)) => {
+					*last = CowStr::from(format!("{}{}", last, text));
+				}
+				(_, event) => events.push(event),
+			}
+		}
+
+		// Mentions aren't linked in code and in text of links and images.
+		let mut literal_depth = 0;
+		let events = events.into_iter().map(|event| match event {
+			Event::Start(tag @ (Tag::CodeBlock(_) | Tag::Link(..) | Tag::Image(..))) => {
+				literal_depth += 1;
+				Event::Start(tag)
+			}
+			Event::End(tag @ (Tag::CodeBlock(_) | Tag::Link(..) | Tag::Image(..))) => {
+				literal_depth -= 1;
+				Event::End(tag)
+			}
+			Event::Text(text) if literal_depth == 0 => {
+				Event::Html(CowStr::from(mentions::link(&text, local_domain, mentions)))
+			}
+			event => event,
+		});
+
+		// Only inline formatting, lists, quotes and code are kept. Anything
+		// else, like headings and images, is removed by the sanitizer.
+		let mut html = String::new();
+		html::push_html(&mut html, events);
+
+		Self {
+			html: sanitize(&html),
+			source: source.to_string(),
+			media_type: MARKDOWN,
+		}
+	}
+}
+
+pub fn escape(text: &str) -> String {
+	text.replace('&', "&amp;")
+		.replace('<', "&lt;")
+		.replace('>', "&gt;")
+		.replace('"', "&quot;")
+		.replace('\'', "&#39;")
+}
+
+pub fn sanitize(html: &str) -> String {
+	SANITIZER.clean(html).to_string()
+}
+
+/// Covers `content` and `contentMap` of the activity and of its object.
+pub fn sanitize_activity(activity: &mut JsonValue) {
+	sanitize_object(activity);
+	if let Some(object) = activity.get_mut("object") {
+		sanitize_object(object);
+	}
+}
+
+fn sanitize_object(object: &mut JsonValue) {
+	if let Some(JsonValue::String(content)) = object.get_mut("content") {
+		*content = sanitize(content);
+	}
+
+	if let Some(JsonValue::Object(content_map)) = object.get_mut("contentMap") {
+		for content in content_map.values_mut() {
+			if let JsonValue::String(content) = content {
+				*content = sanitize(content);
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::routines::Handle;
+	use url::Url;
+
+	fn render(source: &str, mentions: &[Mention]) -> String {
+		Content::from_markdown(source, "local.test", mentions).html
+	}
+
+	#[test]
+	fn shows_html_in_source_as_text() {
+		let html = render(
+			"<script>alert(1)</script> <img src=x onerror=alert(1)>",
+			&[],
+		);
+		assert!(!html.contains("<script"), "{}", html);
+		assert!(!html.contains("<img"), "{}", html);
+		assert!(html.contains("&lt;script&gt;"), "{}", html);
+	}
+
+	#[test]
+	fn removes_disallowed_html() {
+		let html =
+			sanitize(r#"<p>hi<script>alert(1)</script><img src="x" onerror="alert(1)"></p>"#);
+		assert_eq!(html, "<p>hi</p>");
+	}
+
+	#[test]
+	fn removes_javascript_links() {
+		let html = render("[click](javascript:alert(1))", &[]);
+		assert!(!html.contains("javascript:"), "{}", html);
+
+		let html = sanitize(r#"<a href="javascript:alert(1)">click</a>"#);
+		assert!(!html.contains("javascript:"), "{}", html);
+	}
+
+	#[test]
+	fn keeps_mention_links() {
+		let mention = Mention {
+			handle: Handle {
+				username: "bob".to_string(),
+				domain: "example.com".to_string(),
+			},
+			actor_url: Url::parse("https://example.com/users/bob").unwrap(),
+		};
+
+		let html = render("hi @bob@example.com", &[mention]);
+		assert_eq!(
+			html.trim_end(),
+			r#"<p>hi <span class="h-card"><a href="https://example.com/users/bob" class="u-url mention" rel="nofollow noopener noreferrer">@<span>bob</span></a></span></p>"#,
+		);
+	}
+}
@@ -13,8 +13,11 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::activitypub::collections::remixes::{Data, Remixes};
+use crate::activitypub::collections::{Collection, CollectionResponse, PageQuery};
+use crate::audience::{self, Audience, Viewer, AUDIENCE_COLUMNS};
 use crate::error::ApiError;
-use crate::{account, AppState};
+use crate::AppState;
 use actix_web::{get, web, HttpRequest};
 use serde_json::Value as JsonValue;
 use sqlx::Row;
@@ -29,42 +32,9 @@ pub async fn get_activity(
 	path: web::Path<String>,
 	req: HttpRequest,
 ) -> Result<web::Json<JsonValue>, ApiError> {
-	let activity_id = path.into_inner();
-	let activity_id = Uuid::parse_str(&activity_id).map_err(|_| ApiError::ResourceNotFound)?;
-
-	let row = sqlx::query("SELECT activity, is_public, to_mentions, cc_mentions FROM activities WHERE id = $1 AND this_instance = TRUE")
-		.bind(activity_id)
-		.fetch_optional(&state.db)
-		.await?;
-	if row.is_none() {
-		return Err(ApiError::ResourceNotFound);
-	}
-	let row = row.unwrap();
-
-	let is_public: bool = row.get(1);
-	if is_public {
-		let activity: JsonValue = row.get(0);
-		return Ok(web::Json(activity));
-	}
-
-	let to: Vec<Uuid> = row.get(2);
-	let cc: Vec<Uuid> = row.get(3);
+	let activity = fetch_visible_activity(&state, &path, &req).await?;
 
-	if let Some(username) = account::ensure_signed_in(&state, &req) {
-		let user_id: Uuid =
-			sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
-				.bind(username)
-				.fetch_one(&state.db)
-				.await?
-				.get(0);
-
-		if to.contains(&user_id) || cc.contains(&user_id) {
-			let activity: JsonValue = row.get(0);
-			return Ok(web::Json(activity));
-		}
-	}
-
-	Err(ApiError::Forbidden)
+	Ok(web::Json(activity))
 }
 
 #[get("/{id}/object")]
@@ -74,48 +44,78 @@ pub async fn get_object(
 	path: web::Path<String>,
 	req: HttpRequest,
 ) -> Result<web::Json<JsonValue>, ApiError> {
-	let activity_id = path.into_inner();
-	let activity_id = Uuid::parse_str(&activity_id).map_err(|_| ApiError::ResourceNotFound)?;
+	let activity = fetch_visible_activity(&state, &path, &req).await?;
+	let mut activity: HashMap<String, JsonValue> = serde_json::from_value(activity)?;
 
-	let row = sqlx::query("SELECT activity, is_public, to_mentions, cc_mentions FROM activities WHERE id = $1 AND this_instance = TRUE")
-		.bind(activity_id)
-		.fetch_optional(&state.db)
-		.await?;
-	if row.is_none() {
-		return Err(ApiError::ResourceNotFound);
-	}
-	let row = row.unwrap();
-
-	let is_public: bool = row.get(1);
-	if is_public {
-		let activity: JsonValue = row.get(0);
-		let mut activity: HashMap<String, JsonValue> = serde_json::from_value(activity)?;
+	Ok(web::Json(
+		activity.remove("object").ok_or(ApiError::OtherBadRequest)?,
+	))
+}
 
-		return Ok(web::Json(
-			activity.remove("object").ok_or(ApiError::OtherBadRequest)?,
-		));
+async fn fetch_visible_activity(
+	state: &AppState,
+	activity_id: &str,
+	req: &HttpRequest,
+) -> Result<JsonValue, ApiError> {
+	let activity_id = Uuid::parse_str(activity_id).map_err(|_| ApiError::ResourceNotFound)?;
+
+	let row = sqlx::query(&format!(
+		"SELECT activity, {} FROM activities WHERE id = $1 AND this_instance = TRUE",
+		AUDIENCE_COLUMNS
+	))
+	.bind(activity_id)
+	.fetch_optional(&state.db)
+	.await?
+	.ok_or(ApiError::ResourceNotFound)?;
+
+	let audience = Audience::from_row(&row, 1);
+
+	// Public activities don't need a session or a signature to be checked.
+	let viewer = if audience.is_public {
+		Viewer::Anonymous
+	} else {
+		Viewer::from_request(state, req).await?
+	};
+
+	if audience::can_view(state, viewer, &audience).await? {
+		Ok(row.get(0))
+	} else {
+		Err(ApiError::Forbidden)
 	}
+}
 
-	let to: Vec<Uuid> = row.get(2);
-	let cc: Vec<Uuid> = row.get(3);
-
-	if let Some(username) = account::ensure_signed_in(&state, &req) {
-		let user_id: Uuid =
-			sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
-				.bind(username)
-				.fetch_one(&state.db)
-				.await?
-				.get(0);
-
-		if to.contains(&user_id) || cc.contains(&user_id) {
-			let activity: JsonValue = row.get(0);
-			let mut activity: HashMap<String, JsonValue> = serde_json::from_value(activity)?;
+#[get("/{id}/object/remixes")]
+#[instrument(skip(state, req))]
+pub async fn get_remixes(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	query: web::Query<PageQuery>,
+	req: HttpRequest,
+) -> Result<CollectionResponse, ApiError> {
+	let activity_id = path.into_inner();
+	let activity_id = Uuid::parse_str(&activity_id).map_err(|_| ApiError::ResourceNotFound)?;
 
-			return Ok(web::Json(
-				activity.remove("object").ok_or(ApiError::OtherBadRequest)?,
-			));
-		}
+	let row = sqlx::query(&format!(
+		"SELECT {} FROM activities WHERE id = $1 AND this_instance = TRUE",
+		AUDIENCE_COLUMNS
+	))
+	.bind(activity_id)
+	.fetch_optional(&state.db)
+	.await?
+	.ok_or(ApiError::ResourceNotFound)?;
+
+	let audience = Audience::from_row(&row, 0);
+	let viewer = if audience.is_public {
+		Viewer::Anonymous
+	} else {
+		Viewer::from_request(&state, &req).await?
+	};
+	if !audience::can_view(&state, viewer, &audience).await? {
+		return Err(ApiError::Forbidden);
 	}
 
-	Err(ApiError::Forbidden)
+	let collection = Collection::new(Remixes::new(state.clone())).with_page_size(query.limit)?;
+	let data = Data { activity_id };
+
+	collection.respond(&query, &data).await
 }
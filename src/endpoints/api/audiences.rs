@@ -0,0 +1,162 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::utils::{check_name, owned_list, signed_in_user};
+use crate::activitypub::object_handlers::utils::{self, RemoteOrLocalId};
+use crate::audience_lists;
+use crate::error::ApiError;
+use crate::lists::{self, Kind, List};
+use crate::state::AppState;
+use crate::url;
+use actix_web::http::header;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AudienceListInfo {
+	/// URL to address memes to.
+	id: String,
+	name: String,
+	members: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct AudienceListBody {
+	name: String,
+	/// Actor URLs or `acct:` URIs.
+	members: Vec<String>,
+}
+
+#[get("/audiences")]
+#[instrument(skip(state, req))]
+pub async fn get_audience_lists(
+	state: web::Data<AppState>,
+	req: HttpRequest,
+) -> Result<web::Json<Vec<AudienceListInfo>>, ApiError> {
+	let (user_id, _) = signed_in_user(&state, &req).await?;
+
+	let mut infos = Vec::new();
+	for list in lists::list(&state, Kind::Audience, user_id).await? {
+		infos.push(audience_list_info(&state, list).await?);
+	}
+
+	Ok(web::Json(infos))
+}
+
+#[post("/audiences")]
+#[instrument(skip(state, req))]
+pub async fn post_audience_list(
+	state: web::Data<AppState>,
+	body: web::Json<AudienceListBody>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let (user_id, _) = signed_in_user(&state, &req).await?;
+	let body = body.into_inner();
+
+	let name = check_name(&body.name, lists::MAX_NAME_LENGTH)?;
+	let members = resolve_members(&state, &body.members).await?;
+
+	let list = lists::create(&state, Kind::Audience, user_id, name).await?;
+	audience_lists::set_members(&state, list.id, &members).await?;
+	let info = audience_list_info(&state, list).await?;
+
+	Ok(HttpResponse::Created()
+		.insert_header((header::LOCATION, info.id.clone()))
+		.json(info))
+}
+
+#[get("/audiences/{id}")]
+#[instrument(skip(state, req))]
+pub async fn get_audience_list(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	req: HttpRequest,
+) -> Result<web::Json<AudienceListInfo>, ApiError> {
+	let (user_id, _) = signed_in_user(&state, &req).await?;
+	let list = owned_list(&state, Kind::Audience, user_id, &path).await?;
+
+	Ok(web::Json(audience_list_info(&state, list).await?))
+}
+
+#[put("/audiences/{id}")]
+#[instrument(skip(state, req))]
+pub async fn put_audience_list(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	body: web::Json<AudienceListBody>,
+	req: HttpRequest,
+) -> Result<web::Json<AudienceListInfo>, ApiError> {
+	let (user_id, _) = signed_in_user(&state, &req).await?;
+	let mut list = owned_list(&state, Kind::Audience, user_id, &path).await?;
+	let body = body.into_inner();
+
+	let name = check_name(&body.name, lists::MAX_NAME_LENGTH)?;
+	let members = resolve_members(&state, &body.members).await?;
+
+	lists::rename(&state, &list, name).await?;
+	audience_lists::set_members(&state, list.id, &members).await?;
+	list.name = name.to_string();
+
+	Ok(web::Json(audience_list_info(&state, list).await?))
+}
+
+#[delete("/audiences/{id}")]
+#[instrument(skip(state, req))]
+pub async fn delete_audience_list(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let (user_id, _) = signed_in_user(&state, &req).await?;
+	let list = owned_list(&state, Kind::Audience, user_id, &path).await?;
+
+	lists::delete(&state, &list).await?;
+
+	Ok(HttpResponse::NoContent().finish())
+}
+
+async fn resolve_members(
+	state: &web::Data<AppState>,
+	members: &[String],
+) -> Result<Vec<Uuid>, ApiError> {
+	if members.len() > lists::MAX_MEMBERS {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	let accounts = utils::resolve_accounts(state, members).await?;
+
+	let mut ids = Vec::with_capacity(accounts.len());
+	for id in accounts {
+		match id {
+			RemoteOrLocalId::Local(id) => ids.push(id),
+			RemoteOrLocalId::Remote(id, _) if state.addressing_policy.allow_remote_recipients => {
+				ids.push(id)
+			}
+			RemoteOrLocalId::Remote(..) => return Err(ApiError::OtherBadRequest),
+		}
+	}
+
+	Ok(ids)
+}
+
+async fn audience_list_info(state: &AppState, list: List) -> Result<AudienceListInfo, ApiError> {
+	Ok(AudienceListInfo {
+		id: url::audience_list(list.id),
+		members: lists::member_urls(state, &list).await?,
+		name: list.name,
+	})
+}
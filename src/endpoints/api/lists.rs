@@ -0,0 +1,161 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::utils::{check_name, owned_list, signed_in_user};
+use crate::activitypub::object_handlers::utils::{self, RemoteOrLocalId};
+use crate::error::ApiError;
+use crate::lists::{self, Kind, List};
+use crate::state::AppState;
+use crate::url;
+use actix_web::http::header;
+use actix_web::{delete, get, post, put, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ListInfo {
+	id: String,
+	name: String,
+	members: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ListBody {
+	name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct ListMemberBody {
+	/// Actor URL or `acct:` URI of a followed account.
+	account: String,
+}
+
+#[get("/lists")]
+#[instrument(skip(state, req))]
+pub async fn get_lists(
+	state: web::Data<AppState>,
+	req: HttpRequest,
+) -> Result<web::Json<Vec<ListInfo>>, ApiError> {
+	let (user_id, username) = signed_in_user(&state, &req).await?;
+
+	let mut infos = Vec::new();
+	for list in lists::list(&state, Kind::Timeline, user_id).await? {
+		infos.push(list_info(&state, &username, list).await?);
+	}
+
+	Ok(web::Json(infos))
+}
+
+#[post("/lists")]
+#[instrument(skip(state, req))]
+pub async fn post_list(
+	state: web::Data<AppState>,
+	body: web::Json<ListBody>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let (user_id, username) = signed_in_user(&state, &req).await?;
+
+	let name = check_name(&body.name, lists::MAX_NAME_LENGTH)?;
+	let list = lists::create(&state, Kind::Timeline, user_id, name).await?;
+	let info = list_info(&state, &username, list).await?;
+
+	Ok(HttpResponse::Created()
+		.insert_header((header::LOCATION, info.id.clone()))
+		.json(info))
+}
+
+#[put("/lists/{id}")]
+#[instrument(skip(state, req))]
+pub async fn put_list(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	body: web::Json<ListBody>,
+	req: HttpRequest,
+) -> Result<web::Json<ListInfo>, ApiError> {
+	let (user_id, username) = signed_in_user(&state, &req).await?;
+	let mut list = owned_list(&state, Kind::Timeline, user_id, &path).await?;
+
+	let name = check_name(&body.name, lists::MAX_NAME_LENGTH)?;
+	lists::rename(&state, &list, name).await?;
+	list.name = name.to_string();
+
+	Ok(web::Json(list_info(&state, &username, list).await?))
+}
+
+#[delete("/lists/{id}")]
+#[instrument(skip(state, req))]
+pub async fn delete_list(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let (user_id, _) = signed_in_user(&state, &req).await?;
+	let list = owned_list(&state, Kind::Timeline, user_id, &path).await?;
+
+	lists::delete(&state, &list).await?;
+
+	Ok(HttpResponse::NoContent().finish())
+}
+
+#[post("/lists/{id}/members")]
+#[instrument(skip(state, req))]
+pub async fn post_list_member(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	body: web::Json<ListMemberBody>,
+	req: HttpRequest,
+) -> Result<web::Json<ListInfo>, ApiError> {
+	let (user_id, username) = signed_in_user(&state, &req).await?;
+	let list = owned_list(&state, Kind::Timeline, user_id, &path).await?;
+
+	let member_id = resolve_account(&state, &body.account).await?;
+	lists::add_member(&state, &list, member_id).await?;
+
+	Ok(web::Json(list_info(&state, &username, list).await?))
+}
+
+#[delete("/lists/{id}/members")]
+#[instrument(skip(state, req))]
+pub async fn delete_list_member(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	query: web::Query<ListMemberBody>,
+	req: HttpRequest,
+) -> Result<web::Json<ListInfo>, ApiError> {
+	let (user_id, username) = signed_in_user(&state, &req).await?;
+	let list = owned_list(&state, Kind::Timeline, user_id, &path).await?;
+
+	let member_id = resolve_account(&state, &query.account).await?;
+	lists::remove_member(&state, &list, member_id).await?;
+
+	Ok(web::Json(list_info(&state, &username, list).await?))
+}
+
+async fn resolve_account(state: &web::Data<AppState>, account: &str) -> Result<Uuid, ApiError> {
+	let accounts = utils::resolve_accounts(state, &[account.to_string()]).await?;
+	match accounts.into_iter().next() {
+		Some(RemoteOrLocalId::Local(id)) | Some(RemoteOrLocalId::Remote(id, _)) => Ok(id),
+		None => Err(ApiError::OtherBadRequest),
+	}
+}
+
+async fn list_info(state: &AppState, username: &str, list: List) -> Result<ListInfo, ApiError> {
+	Ok(ListInfo {
+		id: url::list(username, list.id),
+		members: lists::member_urls(state, &list).await?,
+		name: list.name,
+	})
+}
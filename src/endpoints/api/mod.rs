@@ -0,0 +1,36 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+pub mod audiences;
+pub mod lists;
+pub mod notifications;
+pub mod resolve;
+pub mod search;
+pub mod similar;
+pub mod templates;
+mod utils;
+
+pub use audiences::{
+	delete_audience_list, get_audience_list, get_audience_lists, post_audience_list,
+	put_audience_list,
+};
+pub use lists::{
+	delete_list, delete_list_member, get_lists, post_list, post_list_member, put_list,
+};
+pub use notifications::get_notifications;
+pub use resolve::get_resolve;
+pub use search::get_search;
+pub use similar::get_similar;
+pub use templates::{get_templates, post_render_template, post_template};
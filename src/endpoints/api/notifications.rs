@@ -0,0 +1,63 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use crate::notifications;
+use crate::state::AppState;
+use crate::{account, url};
+use actix_web::{get, web, HttpRequest};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+const LIMIT: i64 = 40;
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationInfo {
+	#[serde(rename = "type")]
+	kind: String,
+	activity: String,
+	created_at: String,
+}
+
+#[get("/notifications")]
+#[instrument(skip(state, req))]
+pub async fn get_notifications(
+	state: web::Data<AppState>,
+	req: HttpRequest,
+) -> Result<web::Json<Vec<NotificationInfo>>, ApiError> {
+	let username = account::ensure_signed_in(&state, &req).ok_or(ApiError::NotSignedIn)?;
+	let user_id: Uuid =
+		sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
+			.bind(&username)
+			.fetch_one(&state.db)
+			.await?
+			.get(0);
+
+	let infos = notifications::list(&state, user_id, LIMIT)
+		.await?
+		.into_iter()
+		.map(|notification| NotificationInfo {
+			kind: notification.kind,
+			activity: url::activitypub_activity(notification.activity_id),
+			created_at: DateTime::<Utc>::from_utc(notification.created_at, Utc).to_rfc3339(),
+		})
+		.collect();
+
+	Ok(web::Json(infos))
+}
@@ -0,0 +1,56 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::{account, routines};
+use actix_web::{get, web, HttpRequest};
+use serde::{Deserialize, Serialize};
+use tracing::instrument;
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct GetResolveQuery {
+	handle: String,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Resolved {
+	handle: String,
+	id: String,
+}
+
+#[get("/resolve")]
+#[instrument(skip(state, req))]
+pub async fn get_resolve(
+	state: web::Data<AppState>,
+	query: web::Query<GetResolveQuery>,
+	req: HttpRequest,
+) -> Result<web::Json<Resolved>, ApiError> {
+	if account::ensure_signed_in(&state, &req).is_none() {
+		return Err(ApiError::NotSignedIn);
+	}
+
+	let handle = routines::Handle::parse(&query.handle).ok_or(ApiError::IncorrectResourceQuery)?;
+	let actor_url = routines::resolve_handle(&state, &handle).await?;
+
+	if handle.domain != state.domain {
+		routines::fetch_remote_actor(&state, &actor_url).await?;
+	}
+
+	Ok(web::Json(Resolved {
+		handle: format!("{}@{}", handle.username, handle.domain),
+		id: actor_url.to_string(),
+	}))
+}
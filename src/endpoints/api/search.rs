@@ -0,0 +1,202 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::audience::VISIBLE_TO_VIEWER;
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::{account, url};
+use actix_web::{get, web, HttpRequest};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+const PAGE_SIZE: i64 = 20;
+const MAX_QUERY_LENGTH: usize = 200;
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchType {
+	#[default]
+	Memes,
+	Users,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchQuery {
+	q: String,
+	#[serde(default, rename = "type")]
+	search_type: SearchType,
+	#[serde(default)]
+	offset: i64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum SearchResult {
+	Meme {
+		id: Option<String>,
+		#[serde(rename = "attributedTo")]
+		attributed_to: String,
+		published: String,
+		name: Option<String>,
+		summary: Option<String>,
+	},
+	User {
+		id: String,
+		username: String,
+		name: Option<String>,
+		bio: Option<String>,
+	},
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SearchResults {
+	items: Vec<SearchResult>,
+	#[serde(rename = "nextOffset")]
+	next_offset: Option<i64>,
+}
+
+/// Memes are searched by name and summary, users by username, name and bio.
+#[get("/search")]
+#[instrument(skip(state, req))]
+pub async fn get_search(
+	state: web::Data<AppState>,
+	query: web::Query<SearchQuery>,
+	req: HttpRequest,
+) -> Result<web::Json<SearchResults>, ApiError> {
+	let username = account::ensure_signed_in(&state, &req).ok_or(ApiError::NotSignedIn)?;
+	let user_id: Uuid =
+		sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
+			.bind(username)
+			.fetch_one(&state.db)
+			.await?
+			.get(0);
+
+	let text = query.q.trim();
+	if text.is_empty() || text.chars().count() > MAX_QUERY_LENGTH || query.offset < 0 {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	// One more result than needed is fetched to find out whether there is a
+	// next page.
+	let mut items = match query.search_type {
+		SearchType::Memes => search_memes(&state, user_id, text, query.offset).await?,
+		SearchType::Users => search_users(&state, text, query.offset).await?,
+	};
+	let next_offset = if items.len() > PAGE_SIZE as usize {
+		items.truncate(PAGE_SIZE as usize);
+		Some(query.offset + PAGE_SIZE)
+	} else {
+		None
+	};
+
+	Ok(web::Json(SearchResults { items, next_offset }))
+}
+
+async fn search_memes(
+	state: &AppState,
+	user_id: Uuid,
+	text: &str,
+	offset: i64,
+) -> Result<Vec<SearchResult>, ApiError> {
+	let query = format!(
+		"
+		SELECT activities.activity->'object'->>'id', activities.published_at, activities.activity->'object'->>'name', activities.activity->'object'->>'summary', users.username, users.this_instance, users.instance_url
+		FROM activities, users, websearch_to_tsquery('simple', $1) AS query
+		WHERE activities.search_vector @@ query
+		AND users.id = activities.user_id
+		AND {}
+		ORDER BY ts_rank(activities.search_vector, query) DESC, activities.published_at DESC
+		LIMIT $3
+		OFFSET $4
+		",
+		VISIBLE_TO_VIEWER
+	);
+
+	let results = sqlx::query(&query)
+		.bind(text)
+		.bind(user_id)
+		.bind(PAGE_SIZE + 1)
+		.bind(offset)
+		.map(|row: PgRow| {
+			let published_at: NaiveDateTime = row.get(1);
+			let username: &str = row.get(4);
+			let this_instance: bool = row.get(5);
+			let instance_url: Option<String> = row.get(6);
+
+			let attributed_to = if this_instance {
+				url::activitypub_actor(username)
+			} else {
+				instance_url.expect("expected `instance_url` to be not null")
+			};
+
+			SearchResult::Meme {
+				id: row.get(0),
+				attributed_to,
+				published: DateTime::<Utc>::from_utc(published_at, Utc).to_rfc3339(),
+				name: row.get(2),
+				summary: row.get(3),
+			}
+		})
+		.fetch_all(&state.db)
+		.await?;
+
+	Ok(results)
+}
+
+async fn search_users(
+	state: &AppState,
+	text: &str,
+	offset: i64,
+) -> Result<Vec<SearchResult>, ApiError> {
+	const QUERY: &str = "
+		SELECT users.username, users.this_instance, users.instance_url, users.name, users.bio
+		FROM users, websearch_to_tsquery('simple', $1) AS query
+		WHERE users.search_vector @@ query
+		ORDER BY ts_rank(users.search_vector, query) DESC, users.username ASC
+		LIMIT $2
+		OFFSET $3
+	";
+
+	let results = sqlx::query(QUERY)
+		.bind(text)
+		.bind(PAGE_SIZE + 1)
+		.bind(offset)
+		.map(|row: PgRow| {
+			let username: String = row.get(0);
+			let this_instance: bool = row.get(1);
+			let instance_url: Option<String> = row.get(2);
+
+			let id = if this_instance {
+				url::activitypub_actor(&username)
+			} else {
+				instance_url.expect("expected `instance_url` to be not null")
+			};
+
+			SearchResult::User {
+				id,
+				username,
+				name: row.get(3),
+				bio: row.get(4),
+			}
+		})
+		.fetch_all(&state.db)
+		.await?;
+
+	Ok(results)
+}
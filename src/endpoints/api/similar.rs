@@ -0,0 +1,112 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::audience::{self, Audience, Viewer, AUDIENCE_COLUMNS};
+use crate::error::ApiError;
+use crate::media::phash;
+use crate::state::AppState;
+use crate::url;
+use actix_web::{get, web, HttpRequest};
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::Serialize;
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Maximum number of similar memes returned.
+const MAX_SIMILAR_MEMES: i64 = 20;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SimilarMeme {
+	id: Option<String>,
+	#[serde(rename = "attributedTo")]
+	attributed_to: String,
+	published: String,
+	distance: u32,
+}
+
+/// Lists similar public memes published before this one, oldest first.
+#[get("/memes/{id}/similar")]
+#[instrument(skip(state, req))]
+pub async fn get_similar(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	req: HttpRequest,
+) -> Result<web::Json<Vec<SimilarMeme>>, ApiError> {
+	let activity_id = path.into_inner();
+	let activity_id = Uuid::parse_str(&activity_id).map_err(|_| ApiError::ResourceNotFound)?;
+
+	let row = sqlx::query(&format!(
+		"SELECT image_hashes.phash, activities.published_at, {} FROM image_hashes, activities WHERE image_hashes.activity_id = $1 AND activities.id = image_hashes.activity_id",
+		AUDIENCE_COLUMNS
+	))
+	.bind(activity_id)
+	.fetch_optional(&state.db)
+	.await?
+	.ok_or(ApiError::ResourceNotFound)?;
+
+	let hash: i64 = row.get(0);
+	let published_at: NaiveDateTime = row.get(1);
+	let audience = Audience::from_row(&row, 2);
+
+	let viewer = if audience.is_public {
+		Viewer::Anonymous
+	} else {
+		Viewer::from_request(&state, &req).await?
+	};
+	if !audience::can_view(&state, viewer, &audience).await? {
+		return Err(ApiError::Forbidden);
+	}
+
+	// Bands only narrow the candidates down, the distance is checked in the
+	// query as well, so that the limit applies to similar memes only. The
+	// number of differing bits is counted in the text form of the XOR of the
+	// hashes, since `bit_count` requires PostgreSQL 14.
+	let similar: Vec<SimilarMeme> = sqlx::query("SELECT image_hashes.phash, activities.activity->'object'->>'id', activities.published_at, users.username, users.this_instance, users.instance_url FROM image_hashes, activities, users WHERE image_hashes.phash_bands && $1 AND image_hashes.activity_id <> $2 AND length(replace((image_hashes.phash # $4)::bit(64)::text, '0', '')) <= $5 AND activities.id = image_hashes.activity_id AND activities.published_at < $3 AND activities.is_public = TRUE AND users.id = activities.user_id ORDER BY activities.published_at ASC LIMIT $6")
+		.bind(phash::bands(hash as u64))
+		.bind(activity_id)
+		.bind(published_at)
+		.bind(hash)
+		.bind(phash::MAX_DISTANCE as i32)
+		.bind(MAX_SIMILAR_MEMES)
+		.fetch_all(&state.db)
+		.await?
+		.into_iter()
+		.map(|row| {
+			let other_hash: i64 = row.get(0);
+			let distance = phash::distance(hash as u64, other_hash as u64);
+
+			let published_at: NaiveDateTime = row.get(2);
+			let username: &str = row.get(3);
+			let this_instance: bool = row.get(4);
+			let instance_url: Option<String> = row.get(5);
+
+			let attributed_to = if this_instance {
+				url::activitypub_actor(username)
+			} else {
+				instance_url.expect("expected `instance_url` to be not null")
+			};
+
+			SimilarMeme {
+				id: row.get(1),
+				attributed_to,
+				published: DateTime::<Utc>::from_utc(published_at, Utc).to_rfc3339(),
+				distance,
+			}
+		})
+		.collect();
+
+	Ok(web::Json(similar))
+}
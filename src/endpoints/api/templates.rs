@@ -0,0 +1,216 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::utils::{check_name, signed_in_user};
+use crate::activitypub::outbox;
+use crate::error::ApiError;
+use crate::media;
+use crate::media::captions::TextBox;
+use crate::media::templates::{self, Template};
+use crate::state::AppState;
+use crate::url;
+use activitystreams::object::ObjectBox;
+use actix_web::http::header;
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::instrument;
+use uuid::Uuid;
+
+const MAX_NAME_LENGTH: usize = 100;
+const MAX_CAPTION_LENGTH: usize = 500;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TemplateInfo {
+	id: String,
+	name: String,
+	url: String,
+	width: Option<i32>,
+	height: Option<i32>,
+	#[serde(rename = "textBoxes")]
+	text_boxes: Vec<TextBox>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct NewTemplate {
+	name: String,
+	media: String,
+	#[serde(rename = "textBoxes")]
+	text_boxes: Vec<TextBox>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RenderTemplate {
+	/// In the order of the text boxes.
+	captions: Vec<String>,
+	name: String,
+	summary: Option<String>,
+	#[serde(rename = "altText")]
+	alt_text: Option<String>,
+	to: Option<Vec<String>>,
+	cc: Option<Vec<String>>,
+}
+
+#[get("/templates")]
+#[instrument(skip(state))]
+pub async fn get_templates(
+	state: web::Data<AppState>,
+) -> Result<web::Json<Vec<TemplateInfo>>, ApiError> {
+	let mut infos = Vec::new();
+	for template in templates::list(&state).await? {
+		infos.push(template_info(&state, template).await?);
+	}
+
+	Ok(web::Json(infos))
+}
+
+#[post("/templates")]
+#[instrument(skip(state, req))]
+pub async fn post_template(
+	state: web::Data<AppState>,
+	body: web::Json<NewTemplate>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let user_id = signed_in_user(&state, &req).await?.0;
+	let body = body.into_inner();
+
+	let name = check_name(&body.name, MAX_NAME_LENGTH)?;
+
+	let media = media::find_by_url(&state, &body.media)
+		.await?
+		.ok_or(ApiError::OtherBadRequest)?;
+	if !media::is_referenced_by(&state, user_id, media.id).await? {
+		return Err(ApiError::Forbidden);
+	}
+	// Captions can only be drawn onto images.
+	if media.is_video() {
+		return Err(ApiError::UnsupportedMediaType);
+	}
+
+	let (width, height) = match (media.width, media.height) {
+		(Some(width), Some(height)) => (u32::try_from(width)?, u32::try_from(height)?),
+		_ => return Err(ApiError::UnsupportedMediaType),
+	};
+	if body.text_boxes.is_empty() || body.text_boxes.len() > templates::MAX_TEXT_BOXES {
+		return Err(ApiError::OtherBadRequest);
+	}
+	for text_box in &body.text_boxes {
+		let fits = text_box.width > 0
+			&& text_box.height > 0
+			&& text_box.x.checked_add(text_box.width) <= Some(width)
+			&& text_box.y.checked_add(text_box.height) <= Some(height);
+		if !fits {
+			return Err(ApiError::OtherBadRequest);
+		}
+	}
+
+	let template = templates::create(&state, user_id, name, media.id, body.text_boxes).await?;
+	let info = template_info(&state, template).await?;
+
+	Ok(HttpResponse::Created()
+		.insert_header((header::LOCATION, info.id.clone()))
+		.json(info))
+}
+
+/// Publishes a meme rendered from a template, linking back to it.
+#[post("/templates/{id}/render")]
+#[instrument(skip(state, req))]
+pub async fn post_render_template(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	body: web::Json<RenderTemplate>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let (user_id, username) = signed_in_user(&state, &req).await?;
+	let body = body.into_inner();
+
+	let id = Uuid::parse_str(&path.into_inner()).map_err(|_| ApiError::ResourceNotFound)?;
+	let template = templates::get(&state, id)
+		.await?
+		.ok_or(ApiError::ResourceNotFound)?;
+
+	let captions: Vec<String> = body
+		.captions
+		.iter()
+		.map(|caption| caption.trim().to_string())
+		.collect();
+	if captions.len() > template.text_boxes.len()
+		|| captions.iter().all(|caption| caption.is_empty())
+		|| captions
+			.iter()
+			.any(|caption| caption.chars().count() > MAX_CAPTION_LENGTH)
+	{
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	// Captions are a good default description of a meme made from a template.
+	let alt_text = match body.alt_text {
+		Some(alt_text) => alt_text,
+		None => format!(
+			"{}: {}",
+			template.name,
+			captions
+				.iter()
+				.filter(|caption| !caption.is_empty())
+				.map(String::as_str)
+				.collect::<Vec<_>>()
+				.join(" / ")
+		),
+	};
+
+	let rendered = templates::render(&state, &template, captions).await?;
+	let media = media::store(&state, user_id, rendered).await?;
+
+	let mut object = json!({
+		"type": "Image",
+		"name": body.name,
+		"url": media.url(&state),
+		"generator": url::template(template.id),
+		"attachment": [{
+			"type": "Image",
+			"url": media.url(&state),
+			"name": alt_text,
+		}],
+	});
+	if let Some(summary) = body.summary {
+		object["summary"] = json!(summary);
+	}
+	if let Some(to) = body.to {
+		object["to"] = json!(to);
+	}
+	if let Some(cc) = body.cc {
+		object["cc"] = json!(cc);
+	}
+
+	let object: ObjectBox =
+		serde_json::from_value(object).map_err(|_| ApiError::OtherBadRequest)?;
+
+	outbox::post_to_outbox(state, user_id, &username, web::Json(object)).await
+}
+
+async fn template_info(state: &AppState, template: Template) -> Result<TemplateInfo, ApiError> {
+	let media = media::get(state, template.media_id)
+		.await?
+		.ok_or(ApiError::InternalServerError)?;
+
+	Ok(TemplateInfo {
+		id: url::template(template.id),
+		name: template.name,
+		url: media.url(state),
+		width: media.width,
+		height: media.height,
+		text_boxes: template.text_boxes,
+	})
+}
@@ -0,0 +1,66 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::account;
+use crate::error::ApiError;
+use crate::lists::{self, Kind, List};
+use crate::state::AppState;
+use actix_web::HttpRequest;
+use sqlx::Row;
+use uuid::Uuid;
+
+pub async fn signed_in_user(
+	state: &AppState,
+	req: &HttpRequest,
+) -> Result<(Uuid, String), ApiError> {
+	let username = account::ensure_signed_in(state, req).ok_or(ApiError::NotSignedIn)?;
+	let user_id: Uuid =
+		sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
+			.bind(&username)
+			.fetch_one(&state.db)
+			.await?
+			.get(0);
+
+	Ok((user_id, username))
+}
+
+/// The name is trimmed and must not be empty.
+pub fn check_name(name: &str, max_length: usize) -> Result<&str, ApiError> {
+	let name = name.trim();
+	if name.is_empty() || name.chars().count() > max_length {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	Ok(name)
+}
+
+pub async fn owned_list(
+	state: &AppState,
+	kind: Kind,
+	user_id: Uuid,
+	id: &str,
+) -> Result<List, ApiError> {
+	let id = Uuid::parse_str(id).map_err(|_| ApiError::ResourceNotFound)?;
+	let list = lists::get(state, kind, id)
+		.await?
+		.ok_or(ApiError::ResourceNotFound)?;
+
+	// Lists are private, so other users are told it doesn't exist.
+	if list.user_id != user_id {
+		return Err(ApiError::ResourceNotFound);
+	}
+
+	Ok(list)
+}
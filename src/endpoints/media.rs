@@ -0,0 +1,137 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use crate::media;
+use crate::state::AppState;
+use actix_files::NamedFile;
+use actix_web::http::header::{self, Accept, Header, HeaderValue, Quality};
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use mime::Mime;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[get("/{key}")]
+#[instrument(skip(state, req))]
+pub async fn get_media(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let key = path.into_inner();
+	let (media_id, variant_name) = match key.split_once('_') {
+		Some((media_id, variant_name)) => (media_id, Some(variant_name)),
+		None => (&*key, None),
+	};
+	let media_id = Uuid::parse_str(media_id).map_err(|_| ApiError::ResourceNotFound)?;
+
+	let media = media::get(&state, media_id)
+		.await?
+		.ok_or(ApiError::ResourceNotFound)?;
+
+	let (key, media_type) = if let Some(variant_name) = variant_name {
+		let variant = media::get_variant(&state, media_id, variant_name)
+			.await?
+			.ok_or(ApiError::ResourceNotFound)?;
+
+		(media.variant_key(&variant.name), variant.media_type)
+	} else {
+		// Serve the most efficient format the client accepts.
+		let accepted = accepted_media_types(&req);
+		let variants = media::get_variants(&state, media_id).await?;
+		let transcoded = media::TRANSCODED_VARIANTS
+			.iter()
+			.filter(|(_, media_type)| accepted.iter().any(|accepted| accepted == media_type))
+			.find_map(|(name, _)| variants.iter().find(|variant| variant.name == *name));
+
+		match transcoded {
+			Some(variant) => (media.variant_key(&variant.name), variant.media_type.clone()),
+			None => (media.key(), media.media_type),
+		}
+	};
+	let media_type: Mime = media_type
+		.parse()
+		.map_err(|_| ApiError::InternalServerError)?;
+
+	let mut response = if let Some(path) = state.media_storage.local_path(&key) {
+		let file = NamedFile::open_async(path).await?;
+		file.set_content_type(media_type).into_response(&req)
+	} else {
+		HttpResponse::Found()
+			.insert_header((header::LOCATION, state.media_storage.url(&key)))
+			.finish()
+	};
+
+	if variant_name.is_none() {
+		response
+			.headers_mut()
+			.insert(header::VARY, HeaderValue::from_static("accept"));
+	}
+
+	Ok(response)
+}
+
+/// MIME types the client explicitly accepts, without wildcards.
+fn accepted_media_types(req: &HttpRequest) -> Vec<String> {
+	let accept = match Accept::parse(req) {
+		Ok(accept) => accept,
+		Err(_) => return Vec::new(),
+	};
+
+	accept
+		.iter()
+		.filter(|item| item.quality > Quality::ZERO)
+		.map(|item| item.item.essence_str().to_string())
+		.filter(|media_type| !media_type.contains('*'))
+		.collect()
+}
+
+#[get("/{id}")]
+#[instrument(skip(state, req))]
+pub async fn get_proxied_media(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let id = Uuid::parse_str(&path).map_err(|_| ApiError::ResourceNotFound)?;
+	let remote_media = media::proxy::get(&state, id)
+		.await?
+		.ok_or(ApiError::ResourceNotFound)?;
+
+	let media_type = match &remote_media.media_type {
+		Some(media_type) => media_type,
+		None => {
+			let fetched = media::proxy::fetch(&state, &remote_media).await?;
+			return Ok(HttpResponse::Ok()
+				.content_type(fetched.media_type)
+				.body(fetched.bytes));
+		}
+	};
+	let media_type: Mime = media_type
+		.parse()
+		.map_err(|_| ApiError::InternalServerError)?;
+
+	media::proxy::touch(&state, remote_media.id).await?;
+
+	let key = remote_media.key();
+	if let Some(path) = state.media_storage.local_path(&key) {
+		let file = NamedFile::open_async(path).await?;
+		Ok(file.set_content_type(media_type).into_response(&req))
+	} else {
+		Ok(HttpResponse::Found()
+			.insert_header((header::LOCATION, state.media_storage.url(&key)))
+			.finish())
+	}
+}
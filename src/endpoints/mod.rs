@@ -15,6 +15,11 @@
 
 pub mod account;
 pub mod activities;
+pub mod api;
+pub mod media;
+pub mod tags;
+pub mod templates;
+pub mod timelines;
 pub mod users;
 pub mod web_finger;
 
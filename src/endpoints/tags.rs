@@ -0,0 +1,37 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::activitypub::collections::tag::{Data, Tag};
+use crate::activitypub::collections::{Collection, CollectionResponse, PageQuery};
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::tags;
+use actix_web::{get, web};
+use tracing::instrument;
+
+#[get("/{tag}")]
+#[instrument(skip(state))]
+pub async fn get_tag(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	query: web::Query<PageQuery>,
+) -> Result<CollectionResponse, ApiError> {
+	let name = tags::normalize(&path.into_inner()).ok_or(ApiError::ResourceNotFound)?;
+
+	let collection = Collection::new(Tag::new(state.clone())).with_page_size(query.limit)?;
+	let data = Data { name };
+
+	collection.respond(&query, &data).await
+}
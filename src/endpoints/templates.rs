@@ -0,0 +1,62 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use crate::media;
+use crate::media::templates;
+use crate::state::AppState;
+use crate::url;
+use actix_web::{get, web};
+use serde_json::{json, Value as JsonValue};
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Memes made from a template link to it.
+#[get("/{id}")]
+#[instrument(skip(state))]
+pub async fn get_template(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+) -> Result<web::Json<JsonValue>, ApiError> {
+	let id = Uuid::parse_str(&path.into_inner()).map_err(|_| ApiError::ResourceNotFound)?;
+	let template = templates::get(&state, id)
+		.await?
+		.ok_or(ApiError::ResourceNotFound)?;
+	let media = media::get(&state, template.media_id)
+		.await?
+		.ok_or(ApiError::InternalServerError)?;
+
+	let username: String = sqlx::query("SELECT username FROM users WHERE id = $1")
+		.bind(template.user_id)
+		.fetch_one(&state.db)
+		.await?
+		.get(0);
+
+	Ok(web::Json(json!({
+		"@context": "https://www.w3.org/ns/activitystreams",
+		"id": url::template(template.id),
+		"type": "Image",
+		"name": template.name,
+		"url": {
+			"type": "Link",
+			"href": media.url(&state),
+			"mediaType": media.media_type,
+			"width": media.width,
+			"height": media.height,
+		},
+		"attributedTo": url::activitypub_actor(&username),
+	})))
+}
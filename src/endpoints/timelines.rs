@@ -0,0 +1,61 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::account;
+use crate::activitypub::collections::timeline::{Data, Timeline};
+use crate::activitypub::collections::{Collection, CollectionResponse, PageQuery};
+use crate::error::ApiError;
+use crate::state::AppState;
+use actix_web::{get, web, HttpRequest};
+use tracing::instrument;
+
+/// Signed-in users also see local-only memes.
+#[get("/local")]
+#[instrument(skip(state, req))]
+pub async fn get_local_timeline(
+	state: web::Data<AppState>,
+	query: web::Query<PageQuery>,
+	req: HttpRequest,
+) -> Result<CollectionResponse, ApiError> {
+	let data = Data::Local {
+		include_local_only: account::ensure_signed_in(&state, &req).is_some(),
+	};
+	get_timeline(state, data, query.into_inner(), &req).await
+}
+
+#[get("/federated")]
+#[instrument(skip(state, req))]
+pub async fn get_federated_timeline(
+	state: web::Data<AppState>,
+	query: web::Query<PageQuery>,
+	req: HttpRequest,
+) -> Result<CollectionResponse, ApiError> {
+	get_timeline(state, Data::Federated, query.into_inner(), &req).await
+}
+
+async fn get_timeline(
+	state: web::Data<AppState>,
+	data: Data,
+	query: PageQuery,
+	req: &HttpRequest,
+) -> Result<CollectionResponse, ApiError> {
+	if state.timelines_require_sign_in && account::ensure_signed_in(&state, req).is_none() {
+		return Err(ApiError::NotSignedIn);
+	}
+
+	let collection = Collection::new(Timeline::new(state.clone())).with_page_size(query.limit)?;
+
+	collection.respond(&query, &data).await
+}
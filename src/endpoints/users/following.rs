@@ -14,31 +14,21 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::activitypub::collections::following::{Data, Following};
-use crate::activitypub::collections::Collection;
+use crate::activitypub::collections::{Collection, CollectionResponse, PageQuery};
 use crate::error::ApiError;
 use crate::state::AppState;
-use activitystreams::collection::{OrderedCollection, OrderedCollectionPage};
-use actix_web::{get, web, Either};
-use serde::Deserialize;
+use actix_web::{get, web};
 use sqlx::Row;
 use tracing::instrument;
 use uuid::Uuid;
 
-#[derive(Clone, Copy, Debug, Deserialize)]
-pub struct GetFollowingQuery {
-	#[serde(default)]
-	page: bool,
-	max_id: Option<i64>,
-	min_id: Option<i64>,
-}
-
 #[get("/{username}/following")]
 #[instrument(skip(state))]
 pub async fn get_following(
 	state: web::Data<AppState>,
 	path: web::Path<String>,
-	query: web::Query<GetFollowingQuery>,
-) -> Result<Either<web::Json<OrderedCollection>, web::Json<OrderedCollectionPage>>, ApiError> {
+	query: web::Query<PageQuery>,
+) -> Result<CollectionResponse, ApiError> {
 	let username = path.into_inner();
 
 	let user_id: Option<Uuid> =
@@ -52,38 +42,8 @@ pub async fn get_following(
 	}
 	let user_id = user_id.unwrap();
 
-	let collection = Collection::new(Following::new(state.clone()));
+	let collection = Collection::new(Following::new(state.clone())).with_page_size(query.limit)?;
 	let data = Data { user_id, username };
 
-	if query.page {
-		if query.max_id.is_none() && query.min_id.is_none() {
-			return collection
-				.first_page(&data)
-				.await
-				.map(|val| Either::Right(web::Json(val)));
-		}
-
-		if query.max_id.is_some() && query.min_id.is_some() {
-			return Err(ApiError::OtherBadRequest);
-		}
-
-		if let Some(max_id) = query.max_id {
-			return collection
-				.max_id_page(max_id, &data)
-				.await
-				.map(|val| Either::Right(web::Json(val)));
-		}
-
-		if let Some(min_id) = query.min_id {
-			return collection
-				.min_id_page(min_id, &data)
-				.await
-				.map(|val| Either::Right(web::Json(val)));
-		}
-	}
-
-	collection
-		.index_page(&data)
-		.await
-		.map(|val| Either::Left(web::Json(val)))
+	collection.respond(&query, &data).await
 }
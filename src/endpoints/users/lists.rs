@@ -0,0 +1,70 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::account;
+use crate::activitypub::collections::list::{Data, List};
+use crate::activitypub::collections::{Collection, CollectionResponse, PageQuery};
+use crate::error::ApiError;
+use crate::lists::{self, Kind};
+use crate::state::AppState;
+use actix_web::{get, web, HttpRequest};
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+#[get("/{username}/lists/{id}")]
+#[instrument(skip(state, req))]
+pub async fn get_list(
+	state: web::Data<AppState>,
+	path: web::Path<(String, String)>,
+	query: web::Query<PageQuery>,
+	req: HttpRequest,
+) -> Result<CollectionResponse, ApiError> {
+	let (username, list_id) = path.into_inner();
+
+	let user_id: Option<Uuid> =
+		sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
+			.bind(&username)
+			.fetch_optional(&state.db)
+			.await?
+			.map(|row| row.get(0));
+	if user_id.is_none() {
+		return Err(ApiError::UserDoesNotExist);
+	}
+	let user_id = user_id.unwrap();
+
+	match account::ensure_signed_in(&state, &req) {
+		Some(session_username) if username == session_username => (),
+		Some(_) => return Err(ApiError::Forbidden),
+		None => return Err(ApiError::NotSignedIn),
+	}
+
+	let list_id = Uuid::parse_str(&list_id).map_err(|_| ApiError::ResourceNotFound)?;
+	let list = lists::get(&state, Kind::Timeline, list_id)
+		.await?
+		.ok_or(ApiError::ResourceNotFound)?;
+	if list.user_id != user_id {
+		return Err(ApiError::ResourceNotFound);
+	}
+
+	let collection = Collection::new(List::new(state.clone())).with_page_size(query.limit)?;
+	let data = Data {
+		user_id,
+		username,
+		list_id,
+	};
+
+	collection.respond(&query, &data).await
+}
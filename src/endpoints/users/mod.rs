@@ -13,17 +13,23 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+pub mod delete_media;
 pub mod followers;
 pub mod following;
 pub mod inbox;
+pub mod lists;
 pub mod outbox;
+pub mod upload_media;
 
+pub use delete_media::delete_user_media;
 pub use followers::get_followers;
 pub use following::get_following;
 pub use inbox::get_inbox;
 pub use inbox::post_inbox;
+pub use lists::get_list;
 pub use outbox::get_outbox;
 pub use outbox::post_outbox;
+pub use upload_media::post_upload_media;
 
 use crate::error::ApiError;
 use crate::state::AppState;
@@ -71,10 +77,7 @@ pub async fn get_user(
 
 	let profile_picture_id: Option<&str> = row.get(2);
 	if let Some(profile_picture_id) = profile_picture_id {
-		user_props.set_icon_xsd_any_uri(format!(
-			"{}://{}/media/{}",
-			state.scheme, state.domain, profile_picture_id
-		))?;
+		user_props.set_icon_xsd_any_uri(state.media_storage.url(profile_picture_id))?;
 	}
 
 	let public_key: &str = row.get(3);
@@ -92,6 +95,12 @@ pub async fn get_user(
 		serde_json::from_value(serialized_data)?;
 	let user_url = url::activitypub_actor(&username);
 
+	deserialized_data.insert(
+		"endpoints".to_string(),
+		serde_json::json!({
+			"uploadMedia": format!("{}/upload-media", user_url),
+		}),
+	);
 	deserialized_data.insert(
 		"publicKey".to_string(),
 		serde_json::json!({
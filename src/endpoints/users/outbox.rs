@@ -15,34 +15,26 @@
 
 use crate::account;
 use crate::activitypub::collections::outbox::{Data, Outbox};
-use crate::activitypub::collections::Collection;
+use crate::activitypub::collections::{Collection, CollectionResponse, PageQuery};
 use crate::activitypub::outbox;
+use crate::audience::Viewer;
 use crate::error::ApiError;
 use crate::state::AppState;
-use activitystreams::collection::{OrderedCollection, OrderedCollectionPage};
 use activitystreams::object::ObjectBox;
 use actix_web::http::header;
-use actix_web::{get, post, web, Either, HttpRequest, HttpResponse};
-use serde::Deserialize;
+use actix_web::{get, post, web, HttpRequest, HttpResponse};
 use sqlx::Row;
 use tracing::instrument;
 use uuid::Uuid;
 
-#[derive(Clone, Copy, Debug, Deserialize)]
-pub struct GetOutboxQuery {
-	#[serde(default)]
-	page: bool,
-	max_id: Option<i64>,
-	min_id: Option<i64>,
-}
-
 #[get("/{username}/outbox")]
-#[instrument(skip(state))]
+#[instrument(skip(state, req))]
 pub async fn get_outbox(
 	state: web::Data<AppState>,
 	path: web::Path<String>,
-	query: web::Query<GetOutboxQuery>,
-) -> Result<Either<web::Json<OrderedCollection>, web::Json<OrderedCollectionPage>>, ApiError> {
+	query: web::Query<PageQuery>,
+	req: HttpRequest,
+) -> Result<CollectionResponse, ApiError> {
 	let username = path.into_inner();
 
 	let user_id: Option<Uuid> =
@@ -56,40 +48,15 @@ pub async fn get_outbox(
 	}
 	let user_id = user_id.unwrap();
 
-	let collection = Collection::new(Outbox::new(state.clone()));
-	let data = Data { user_id, username };
-
-	if query.page {
-		if query.max_id.is_none() && query.min_id.is_none() {
-			return collection
-				.first_page(&data)
-				.await
-				.map(|val| Either::Right(web::Json(val)));
-		}
-
-		if query.max_id.is_some() && query.min_id.is_some() {
-			return Err(ApiError::OtherBadRequest);
-		}
-
-		if let Some(max_id) = query.max_id {
-			return collection
-				.max_id_page(max_id, &data)
-				.await
-				.map(|val| Either::Right(web::Json(val)));
-		}
-
-		if let Some(min_id) = query.min_id {
-			return collection
-				.min_id_page(min_id, &data)
-				.await
-				.map(|val| Either::Right(web::Json(val)));
-		}
-	}
+	let collection = Collection::new(Outbox::new(state.clone())).with_page_size(query.limit)?;
+	let viewer = Viewer::from_request(&state, &req).await?;
+	let data = Data {
+		user_id,
+		username,
+		viewer,
+	};
 
-	collection
-		.index_page(&data)
-		.await
-		.map(|val| Either::Left(web::Json(val)))
+	collection.respond(&query, &data).await
 }
 
 #[post("/{username}/outbox")]
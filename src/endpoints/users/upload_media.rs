@@ -0,0 +1,187 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::account;
+use crate::activitypub::outbox;
+use crate::error::ApiError;
+use crate::media;
+use crate::state::AppState;
+use activitystreams::object::ObjectBox;
+use actix_multipart::Multipart;
+use actix_web::http::header;
+use actix_web::{post, web, HttpRequest, HttpResponse};
+use futures::TryStreamExt;
+use serde_json::{json, Value as JsonValue};
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+const MAX_OBJECT_SIZE: usize = 65536;
+const MAX_ALT_TEXT_LENGTH: usize = 1500;
+
+#[post("/{username}/upload-media")]
+#[instrument(skip(state, payload, req))]
+pub async fn post_upload_media(
+	state: web::Data<AppState>,
+	path: web::Path<String>,
+	mut payload: Multipart,
+	req: HttpRequest,
+) -> Result<HttpResponse, ApiError> {
+	let username = path.into_inner();
+	let user_id: Option<Uuid> =
+		sqlx::query("SELECT id FROM users WHERE username = $1 AND this_instance = TRUE")
+			.bind(&username)
+			.fetch_optional(&state.db)
+			.await?
+			.map(|row| row.get(0));
+	if user_id.is_none() {
+		return Err(ApiError::UserDoesNotExist);
+	}
+	let user_id = user_id.unwrap();
+
+	match account::ensure_signed_in(&state, &req) {
+		Some(session_username) if username == session_username => (),
+		Some(_) => return Err(ApiError::Forbidden),
+		None => return Err(ApiError::NotSignedIn),
+	}
+
+	let mut file: Option<Vec<u8>> = None;
+	let mut poster: Option<Vec<u8>> = None;
+	let mut object: Option<JsonValue> = None;
+	let mut alt_text: Option<String> = None;
+
+	while let Some(mut field) = payload.try_next().await? {
+		let name = field
+			.content_disposition()
+			.get_name()
+			.map(|name| name.to_string());
+
+		match name.as_deref() {
+			Some("file") => {
+				// Whether the file is an image or a video is only known once
+				// it is read.
+				let max_size = state.max_upload_size.max(state.max_video_upload_size);
+
+				let mut bytes = Vec::new();
+				while let Some(chunk) = field.try_next().await? {
+					if bytes.len() + chunk.len() > max_size {
+						return Err(ApiError::FileTooLarge);
+					}
+
+					bytes.extend_from_slice(&chunk);
+				}
+
+				file = Some(bytes);
+			}
+			Some("poster") => {
+				let mut bytes = Vec::new();
+				while let Some(chunk) = field.try_next().await? {
+					if bytes.len() + chunk.len() > state.max_upload_size {
+						return Err(ApiError::FileTooLarge);
+					}
+
+					bytes.extend_from_slice(&chunk);
+				}
+
+				poster = Some(bytes);
+			}
+			Some("object") => {
+				let mut bytes = Vec::new();
+				while let Some(chunk) = field.try_next().await? {
+					if bytes.len() + chunk.len() > MAX_OBJECT_SIZE {
+						return Err(ApiError::OtherBadRequest);
+					}
+
+					bytes.extend_from_slice(&chunk);
+				}
+
+				object =
+					Some(serde_json::from_slice(&bytes).map_err(|_| ApiError::OtherBadRequest)?);
+			}
+			Some("alt") => {
+				let mut bytes = Vec::new();
+				while let Some(chunk) = field.try_next().await? {
+					// UTF-8 takes at most 4 bytes per character.
+					if bytes.len() + chunk.len() > MAX_ALT_TEXT_LENGTH * 4 {
+						return Err(ApiError::OtherBadRequest);
+					}
+
+					bytes.extend_from_slice(&chunk);
+				}
+
+				let text = String::from_utf8(bytes).map_err(|_| ApiError::OtherBadRequest)?;
+				if text.chars().count() > MAX_ALT_TEXT_LENGTH {
+					return Err(ApiError::OtherBadRequest);
+				}
+
+				let text = text.trim();
+				if !text.is_empty() {
+					alt_text = Some(text.to_string());
+				}
+			}
+			_ => return Err(ApiError::OtherBadRequest),
+		}
+	}
+
+	let file = file.ok_or(ApiError::OtherBadRequest)?;
+	let media = if media::container::probe(&file).is_some() {
+		if file.len() > state.max_video_upload_size {
+			return Err(ApiError::FileTooLarge);
+		}
+
+		media::store_video(&state, user_id, file, poster).await?
+	} else {
+		if file.len() > state.max_upload_size {
+			return Err(ApiError::FileTooLarge);
+		}
+		// Only videos have posters.
+		if poster.is_some() {
+			return Err(ApiError::OtherBadRequest);
+		}
+
+		media::store(&state, user_id, file).await?
+	};
+	if let Some(alt_text) = &alt_text {
+		media::set_alt_text(&state, user_id, media.id, alt_text).await?;
+	} else {
+		alt_text = media::get_alt_text(&state, user_id, media.id).await?;
+	}
+
+	// If an object was sent alongside the file, post it to the outbox with the
+	// URL of the uploaded file, as described in ActivityPub's uploadMedia.
+	if let Some(mut object) = object {
+		object
+			.as_object_mut()
+			.ok_or(ApiError::OtherBadRequest)?
+			.insert("url".to_string(), JsonValue::String(media.url(&state)));
+
+		let object: ObjectBox =
+			serde_json::from_value(object).map_err(|_| ApiError::OtherBadRequest)?;
+
+		return outbox::post_to_outbox(state, user_id, &username, web::Json(object)).await;
+	}
+
+	Ok(HttpResponse::Created()
+		.insert_header((header::LOCATION, media.url(&state)))
+		.json(json!({
+			"id": media.url(&state),
+			"mediaType": media.media_type,
+			"width": media.width,
+			"height": media.height,
+			"blurhash": media.blurhash,
+			"duration": media.duration_ms,
+			"name": alt_text,
+		})))
+}
@@ -14,6 +14,7 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use activitystreams::primitives::XsdAnyUriError;
+use actix_multipart::MultipartError;
 use actix_web::error::PayloadError;
 use actix_web::http::StatusCode;
 use actix_web::rt::task::JoinError;
@@ -43,11 +44,20 @@ pub enum ApiError {
 	PasswordMustNotBeEmpty,
 	IncorrectPassword,
 	NotSignedIn,
+	InvalidSignature,
 	Forbidden,
 	ResourceNotFound,
 	BadUrl,
 	UnexpectedResponseFromFederatedServer,
 	FailedDeliveryDueToNetworkError,
+	FileTooLarge,
+	UnsupportedMediaType,
+	StorageQuotaExceeded,
+	AltTextRequired,
+	VideoTooLong,
+	UnknownVideoDuration,
+	PosterRequired,
+	UnsupportedActorType,
 	OtherBadRequest,
 }
 
@@ -66,6 +76,7 @@ impl fmt::Display for ApiError {
 			Self::PasswordMustNotBeEmpty => write!(f, "Password must not be empty."),
 			Self::IncorrectPassword => write!(f, "Incorrect password."),
 			Self::NotSignedIn => write!(f, "Not signed in."),
+			Self::InvalidSignature => write!(f, "Invalid signature."),
 			Self::Forbidden => write!(f, "Forbidden."),
 			Self::ResourceNotFound => write!(f, "Resource not found."),
 			Self::BadUrl => write!(f, "Bad URL."),
@@ -75,6 +86,14 @@ impl fmt::Display for ApiError {
 			Self::FailedDeliveryDueToNetworkError => {
 				write!(f, "Failed delivery due to network error.")
 			}
+			Self::FileTooLarge => write!(f, "File is too large."),
+			Self::UnsupportedMediaType => write!(f, "Unsupported media type."),
+			Self::StorageQuotaExceeded => write!(f, "Storage quota exceeded."),
+			Self::AltTextRequired => write!(f, "Alt text is required."),
+			Self::VideoTooLong => write!(f, "Video is too long."),
+			Self::UnknownVideoDuration => write!(f, "Duration of the video is unknown."),
+			Self::PosterRequired => write!(f, "Poster image is required for this video."),
+			Self::UnsupportedActorType => write!(f, "Unsupported actor type."),
 			Self::OtherBadRequest => write!(f, "Bad request."),
 		}
 	}
@@ -87,8 +106,12 @@ impl ResponseError for ApiError {
 		match *self {
 			Self::UserDoesNotExist => StatusCode::NOT_FOUND,
 			Self::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
+			Self::InvalidSignature => StatusCode::UNAUTHORIZED,
 			Self::Forbidden => StatusCode::FORBIDDEN,
 			Self::ResourceNotFound => StatusCode::NOT_FOUND,
+			Self::FileTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+			Self::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+			Self::StorageQuotaExceeded => StatusCode::PAYLOAD_TOO_LARGE,
 			_ => StatusCode::BAD_REQUEST,
 		}
 	}
@@ -157,6 +180,13 @@ impl From<PayloadError> for ApiError {
 	}
 }
 
+impl From<MultipartError> for ApiError {
+	fn from(err: MultipartError) -> Self {
+		error!(?err, "Failed to read a multipart body");
+		Self::OtherBadRequest
+	}
+}
+
 impl From<JoinError> for ApiError {
 	fn from(err: JoinError) -> Self {
 		error!(?err, "Failed to join tasks");
@@ -0,0 +1,142 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use crate::state::AppState;
+use chrono::NaiveDateTime;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, Row};
+use tracing::instrument;
+use uuid::Uuid;
+
+const BACKFILL_BATCH_SIZE: i64 = 500;
+
+/// Addressed directly, to followers of someone they follow or to an audience
+/// list they are in.
+#[instrument(skip(conn))]
+pub async fn fan_out(conn: &mut PgConnection, activity_ids: &[Uuid]) -> Result<(), ApiError> {
+	const QUERY: &str = "
+		INSERT INTO feed_entries (user_id, activity_id, published_at)
+		SELECT users.id, activities.id, activities.published_at
+		FROM activities, users
+		WHERE activities.id = ANY($1)
+		AND users.this_instance = TRUE
+		AND users.id IN (
+			SELECT unnest(activities.to_mentions || activities.cc_mentions)
+			UNION
+			SELECT subject_user_id
+			FROM follows
+			WHERE object_user_id = ANY(activities.to_followers_of || activities.cc_followers_of)
+			AND pending = FALSE
+			UNION
+			SELECT member_user_id
+			FROM audience_list_members
+			WHERE list_id = ANY(activities.audience_lists)
+		)
+		ON CONFLICT DO NOTHING
+	";
+
+	sqlx::query(QUERY).bind(activity_ids).execute(conn).await?;
+
+	Ok(())
+}
+
+/// So that the activities are only in the feeds of users who can view them.
+#[instrument(skip(conn))]
+pub async fn refresh_audience_list(conn: &mut PgConnection, list_id: Uuid) -> Result<(), ApiError> {
+	let activity_ids: Vec<Uuid> =
+		sqlx::query("SELECT id FROM activities WHERE audience_lists @> ARRAY[$1]::uuid[]")
+			.bind(list_id)
+			.map(|row: PgRow| row.get(0))
+			.fetch_all(&mut *conn)
+			.await?;
+
+	sqlx::query("DELETE FROM feed_entries WHERE activity_id = ANY($1)")
+		.bind(&activity_ids)
+		.execute(&mut *conn)
+		.await?;
+
+	fan_out(conn, &activity_ids).await
+}
+
+#[instrument(skip(state))]
+pub async fn add_followed(
+	state: &AppState,
+	follower_id: Uuid,
+	followed_id: Uuid,
+) -> Result<(), ApiError> {
+	const QUERY: &str = "
+		INSERT INTO feed_entries (user_id, activity_id, published_at)
+		SELECT users.id, activities.id, activities.published_at
+		FROM activities, users
+		WHERE users.id = $1
+		AND users.this_instance = TRUE
+		AND (activities.to_followers_of @> ARRAY[$2]::uuid[] OR activities.cc_followers_of @> ARRAY[$2]::uuid[])
+		ON CONFLICT DO NOTHING
+	";
+
+	sqlx::query(QUERY)
+		.bind(follower_id)
+		.bind(followed_id)
+		.execute(&state.db)
+		.await?;
+
+	Ok(())
+}
+
+/// Returns `false` once there are no activities left to add.
+#[instrument(skip(state))]
+pub async fn backfill_step(state: &AppState) -> Result<bool, ApiError> {
+	let row =
+		sqlx::query("SELECT cursor_published_at, cursor_activity_id, is_done FROM feed_backfill")
+			.fetch_optional(&state.db)
+			.await?;
+	let (cursor_published_at, cursor_activity_id): (NaiveDateTime, Uuid) = match row {
+		Some(row) if !row.get::<bool, _>(2) => (row.get(0), row.get(1)),
+		_ => return Ok(false),
+	};
+
+	let batch: Vec<(NaiveDateTime, Uuid)> = sqlx::query("SELECT published_at, id FROM activities WHERE (published_at, id) < ($1, $2) ORDER BY published_at DESC, id DESC LIMIT $3")
+		.bind(cursor_published_at)
+		.bind(cursor_activity_id)
+		.bind(BACKFILL_BATCH_SIZE)
+		.map(|row: PgRow| (row.get(0), row.get(1)))
+		.fetch_all(&state.db)
+		.await?;
+
+	let activity_ids: Vec<Uuid> = batch.iter().map(|(_, id)| *id).collect();
+	fan_out(&mut *state.db.acquire().await?, &activity_ids).await?;
+
+	match batch.last() {
+		Some((published_at, activity_id)) => {
+			sqlx::query(
+				"UPDATE feed_backfill SET cursor_published_at = $1, cursor_activity_id = $2",
+			)
+			.bind(published_at)
+			.bind(activity_id)
+			.execute(&state.db)
+			.await?;
+
+			Ok(true)
+		}
+		None => {
+			sqlx::query("UPDATE feed_backfill SET is_done = TRUE")
+				.execute(&state.db)
+				.await?;
+
+			Ok(false)
+		}
+	}
+}
@@ -0,0 +1,205 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use actix_tls::connect::tcp::TcpConnectorService;
+use actix_tls::connect::{ConnectError, ConnectInfo, Connection, ResolverService};
+use actix_web::dev::Service;
+use actix_web::http::Uri;
+use awc::{ClientBuilder, Connector};
+use futures::future::LocalBoxFuture;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::task::{Context, Poll};
+use tokio::net::TcpStream;
+use url::{Host, Url};
+
+/// Checks resolved addresses right before connecting to them, so that DNS
+/// can't change in between.
+#[derive(Clone, Default)]
+pub struct PublicConnector {
+	resolver: ResolverService,
+	tcp: TcpConnectorService,
+}
+
+impl Service<ConnectInfo<Uri>> for PublicConnector {
+	type Response = Connection<Uri, TcpStream>;
+	type Error = ConnectError;
+	type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+	fn poll_ready(&self, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Poll::Ready(Ok(()))
+	}
+
+	fn call(&self, req: ConnectInfo<Uri>) -> Self::Future {
+		let resolved = self.resolver.call(req);
+		let tcp = self.tcp;
+
+		Box::pin(async move {
+			let req = resolved.await?;
+			if !req.addrs().all(|address| is_public_address(address.ip())) {
+				return Err(ConnectError::Io(io::Error::new(
+					io::ErrorKind::PermissionDenied,
+					"address isn't public",
+				)));
+			}
+
+			tcp.call(req).await
+		})
+	}
+}
+
+pub fn builder() -> ClientBuilder<PublicConnector> {
+	awc::Client::builder().connector(Connector::new().connector(PublicConnector::default()))
+}
+
+/// Rejects URLs that aren't HTTPS or point to a non-public IP address.
+pub fn check_url(url: &Url) -> Result<(), ApiError> {
+	if url.scheme() != "https" {
+		return Err(ApiError::BadUrl);
+	}
+
+	let is_public = match url.host() {
+		Some(Host::Ipv4(address)) => is_public_address(IpAddr::V4(address)),
+		Some(Host::Ipv6(address)) => is_public_address(IpAddr::V6(address)),
+		Some(Host::Domain(_)) => true,
+		None => false,
+	};
+	if !is_public {
+		return Err(ApiError::BadUrl);
+	}
+
+	Ok(())
+}
+
+fn is_public_address(address: IpAddr) -> bool {
+	match address {
+		IpAddr::V4(address) => is_public_ipv4_address(address),
+		IpAddr::V6(address) => {
+			// Addresses that embed an IPv4 address reach whatever that address
+			// does.
+			if let Some(address) = embedded_ipv4_address(address) {
+				return is_public_ipv4_address(address);
+			}
+
+			let [first_segment, second_segment, third_segment, ..] = address.segments();
+			!(address.is_loopback()
+				|| address.is_unspecified()
+				|| address.is_multicast()
+				// Unique local addresses, fc00::/7.
+				|| first_segment & 0xfe00 == 0xfc00
+				// Link-local addresses, fe80::/10.
+				|| first_segment & 0xffc0 == 0xfe80
+				// Local-use NAT64 prefix, 64:ff9b:1::/48.
+				|| (first_segment == 0x64 && second_segment == 0xff9b && third_segment == 1))
+		}
+	}
+}
+
+fn embedded_ipv4_address(address: Ipv6Addr) -> Option<Ipv4Addr> {
+	if let Some(address) = address.to_ipv4_mapped() {
+		return Some(address);
+	}
+
+	let segments = address.segments();
+	let octets = address.octets();
+	match segments {
+		// NAT64, 64:ff9b::/96.
+		[0x64, 0xff9b, 0, 0, 0, 0, ..] => Some(Ipv4Addr::new(
+			octets[12], octets[13], octets[14], octets[15],
+		)),
+		// 6to4, 2002::/16.
+		[0x2002, ..] => Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5])),
+		_ => None,
+	}
+}
+
+fn is_public_ipv4_address(address: Ipv4Addr) -> bool {
+	let [first_octet, second_octet, ..] = address.octets();
+	!(address.is_loopback()
+		|| address.is_private()
+		|| address.is_link_local()
+		|| address.is_unspecified()
+		|| address.is_broadcast()
+		|| address.is_multicast()
+		|| address.is_documentation()
+		// Carrier-grade NAT, 100.64.0.0/10.
+		|| (first_octet == 100 && second_octet & 0xc0 == 64))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn rejects_non_public_addresses() {
+		for address in [
+			"127.0.0.1",
+			"10.1.2.3",
+			"172.16.0.1",
+			"192.168.1.1",
+			"169.254.169.254",
+			"100.64.0.1",
+			"0.0.0.0",
+			"::1",
+			"::",
+			"fc00::1",
+			"fd12:3456::1",
+			"fe80::1",
+			"::ffff:127.0.0.1",
+			"::ffff:10.0.0.1",
+			"64:ff9b::7f00:1",
+			"64:ff9b::a9fe:a9fe",
+			"64:ff9b:1::1",
+			"2002:7f00:1::",
+			"2002:c0a8:101::1",
+		] {
+			assert!(!is_public_address(address.parse().unwrap()), "{}", address);
+		}
+	}
+
+	#[test]
+	fn accepts_public_addresses() {
+		for address in [
+			"93.184.216.34",
+			"1.1.1.1",
+			"2606:4700:4700::1111",
+			"64:ff9b::5db8:d822",
+			"2002:5db8:d822::1",
+		] {
+			assert!(is_public_address(address.parse().unwrap()), "{}", address);
+		}
+	}
+
+	#[test]
+	fn rejects_private_urls() {
+		for url in [
+			"http://93.184.216.34/a.png",
+			"https://127.0.0.1/a.png",
+			"https://[::1]/a.png",
+			"https://10.0.0.1:8443/a.png",
+		] {
+			assert!(check_url(&Url::parse(url).unwrap()).is_err(), "{}", url);
+		}
+	}
+
+	#[actix_web::test]
+	async fn does_not_connect_to_private_hosts() {
+		let client = builder().finish();
+		for url in ["https://localhost/", "https://127.0.0.1:1/"] {
+			assert!(client.get(url).send().await.is_err(), "{}", url);
+		}
+	}
+}
@@ -0,0 +1,239 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Named lists of accounts. Timeline lists show memes by their members, and
+//! audience lists can be addressed memes to.
+
+use crate::error::ApiError;
+use crate::feed;
+use crate::state::AppState;
+use crate::url;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Of each kind, per user.
+pub const MAX_LISTS: i64 = 50;
+pub const MAX_MEMBERS: usize = 1000;
+pub const MAX_NAME_LENGTH: usize = 100;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Kind {
+	Timeline,
+	Audience,
+}
+
+impl Kind {
+	fn table(self) -> &'static str {
+		match self {
+			Self::Timeline => "lists",
+			Self::Audience => "audience_lists",
+		}
+	}
+
+	fn members_table(self) -> &'static str {
+		match self {
+			Self::Timeline => "list_members",
+			Self::Audience => "audience_list_members",
+		}
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct List {
+	pub id: Uuid,
+	pub user_id: Uuid,
+	pub name: String,
+	pub kind: Kind,
+}
+
+impl List {
+	fn from_row(row: PgRow, kind: Kind) -> Self {
+		Self {
+			id: row.get(0),
+			user_id: row.get(1),
+			name: row.get(2),
+			kind,
+		}
+	}
+}
+
+#[instrument(skip(state))]
+pub async fn create(
+	state: &AppState,
+	kind: Kind,
+	user_id: Uuid,
+	name: &str,
+) -> Result<List, ApiError> {
+	let count: i64 = sqlx::query(&format!(
+		"SELECT COUNT(1) FROM {} WHERE user_id = $1",
+		kind.table()
+	))
+	.bind(user_id)
+	.fetch_one(&state.db)
+	.await?
+	.get(0);
+	if count >= MAX_LISTS {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	let list = List {
+		id: Uuid::new_v4(),
+		user_id,
+		name: name.to_string(),
+		kind,
+	};
+
+	sqlx::query(&format!(
+		"INSERT INTO {} (id, user_id, name) VALUES ($1, $2, $3)",
+		kind.table()
+	))
+	.bind(list.id)
+	.bind(list.user_id)
+	.bind(&list.name)
+	.execute(&state.db)
+	.await?;
+
+	Ok(list)
+}
+
+#[instrument(skip(state))]
+pub async fn get(state: &AppState, kind: Kind, id: Uuid) -> Result<Option<List>, ApiError> {
+	let list = sqlx::query(&format!(
+		"SELECT id, user_id, name FROM {} WHERE id = $1",
+		kind.table()
+	))
+	.bind(id)
+	.map(|row| List::from_row(row, kind))
+	.fetch_optional(&state.db)
+	.await?;
+
+	Ok(list)
+}
+
+#[instrument(skip(state))]
+pub async fn list(state: &AppState, kind: Kind, user_id: Uuid) -> Result<Vec<List>, ApiError> {
+	let lists = sqlx::query(&format!(
+		"SELECT id, user_id, name FROM {} WHERE user_id = $1 ORDER BY name ASC",
+		kind.table()
+	))
+	.bind(user_id)
+	.map(|row| List::from_row(row, kind))
+	.fetch_all(&state.db)
+	.await?;
+
+	Ok(lists)
+}
+
+#[instrument(skip(state))]
+pub async fn rename(state: &AppState, list: &List, name: &str) -> Result<(), ApiError> {
+	sqlx::query(&format!(
+		"UPDATE {} SET name = $1 WHERE id = $2",
+		list.kind.table()
+	))
+	.bind(name)
+	.bind(list.id)
+	.execute(&state.db)
+	.await?;
+
+	Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn delete(state: &AppState, list: &List) -> Result<(), ApiError> {
+	let mut tx = state.db.begin().await?;
+
+	sqlx::query(&format!("DELETE FROM {} WHERE id = $1", list.kind.table()))
+		.bind(list.id)
+		.execute(&mut tx)
+		.await?;
+	if list.kind == Kind::Audience {
+		feed::refresh_audience_list(&mut tx, list.id).await?;
+	}
+
+	tx.commit().await?;
+	Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn member_urls(state: &AppState, list: &List) -> Result<Vec<String>, ApiError> {
+	let query = format!("SELECT users.username, users.this_instance, users.instance_url FROM {0}, users WHERE {0}.list_id = $1 AND users.id = {0}.member_user_id ORDER BY users.username ASC", list.kind.members_table());
+	let urls = sqlx::query(&query)
+		.bind(list.id)
+		.map(|row: PgRow| {
+			let username: &str = row.get(0);
+			let this_instance: bool = row.get(1);
+			let instance_url: Option<String> = row.get(2);
+
+			if this_instance {
+				url::activitypub_actor(username)
+			} else {
+				instance_url.expect("expected `instance_url` to be not null")
+			}
+		})
+		.fetch_all(&state.db)
+		.await?;
+
+	Ok(urls)
+}
+
+/// The owner of the list must be following the account.
+#[instrument(skip(state))]
+pub async fn add_member(state: &AppState, list: &List, member_id: Uuid) -> Result<(), ApiError> {
+	let is_following: bool = sqlx::query("SELECT EXISTS (SELECT 1 FROM follows WHERE subject_user_id = $1 AND object_user_id = $2 AND pending = FALSE)")
+		.bind(list.user_id)
+		.bind(member_id)
+		.fetch_one(&state.db)
+		.await?
+		.get(0);
+	if !is_following {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	// Adding an account that is already a member doesn't change the count.
+	let count: i64 = sqlx::query(
+		"SELECT COUNT(1) FROM list_members WHERE list_id = $1 AND member_user_id <> $2",
+	)
+	.bind(list.id)
+	.bind(member_id)
+	.fetch_one(&state.db)
+	.await?
+	.get(0);
+	if count as usize + 1 > MAX_MEMBERS {
+		return Err(ApiError::OtherBadRequest);
+	}
+
+	sqlx::query(
+		"INSERT INTO list_members (list_id, member_user_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+	)
+	.bind(list.id)
+	.bind(member_id)
+	.execute(&state.db)
+	.await?;
+
+	Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn remove_member(state: &AppState, list: &List, member_id: Uuid) -> Result<(), ApiError> {
+	sqlx::query("DELETE FROM list_members WHERE list_id = $1 AND member_user_id = $2")
+		.bind(list.id)
+		.bind(member_id)
+		.execute(&state.db)
+		.await?;
+
+	Ok(())
+}
@@ -17,16 +17,25 @@
 
 mod account;
 mod activitypub;
+mod audience;
+mod audience_lists;
 mod config;
+mod content;
 mod endpoints;
 mod error;
+mod feed;
+mod http_client;
+mod lists;
+mod media;
+mod mentions;
+mod notifications;
 mod routines;
 mod signatures;
 mod state;
+mod tags;
 mod url;
 
 use config::Config;
-use routines::delivery;
 use state::AppState;
 
 use actix_web::{rt as actix_rt, web, App, HttpServer};
@@ -63,7 +72,9 @@ async fn run() -> Result<(), Box<dyn Error>> {
 	MIGRATOR.run(&state.db).await?;
 
 	url::init(&state);
-	actix_rt::spawn(delivery::retry_deliveries(state.clone()));
+	actix_rt::spawn(routines::retry_deliveries(state.clone()));
+	actix_rt::spawn(routines::prune_media_cache(state.clone()));
+	actix_rt::spawn(routines::backfill_feeds(state.clone()));
 
 	HttpServer::new(move || {
 		App::new()
@@ -73,17 +84,51 @@ async fn run() -> Result<(), Box<dyn Error>> {
 					.service(endpoints::users::get_user)
 					.service(endpoints::users::get_inbox)
 					.service(endpoints::users::post_inbox)
+					.service(endpoints::users::get_list)
 					.service(endpoints::users::get_outbox)
 					.service(endpoints::users::post_outbox)
 					.service(endpoints::users::get_followers)
-					.service(endpoints::users::get_following),
+					.service(endpoints::users::get_following)
+					.service(endpoints::users::post_upload_media)
+					.service(endpoints::users::delete_user_media),
 			)
 			.service(
 				web::scope("/activities")
 					.service(endpoints::activities::get_activity)
-					.service(endpoints::activities::get_object),
+					.service(endpoints::activities::get_object)
+					.service(endpoints::activities::get_remixes),
 			)
+			.service(web::scope("/media").service(endpoints::media::get_media))
+			.service(web::scope("/templates").service(endpoints::templates::get_template))
+			.service(web::scope("/tags").service(endpoints::tags::get_tag))
+			.service(
+				web::scope("/timelines")
+					.service(endpoints::timelines::get_local_timeline)
+					.service(endpoints::timelines::get_federated_timeline),
+			)
+			.service(web::scope("/proxy").service(endpoints::media::get_proxied_media))
 			.service(endpoints::get_web_finger)
+			.service(
+				web::scope("/api")
+					.service(endpoints::api::get_resolve)
+					.service(endpoints::api::get_search)
+					.service(endpoints::api::get_similar)
+					.service(endpoints::api::get_templates)
+					.service(endpoints::api::post_template)
+					.service(endpoints::api::post_render_template)
+					.service(endpoints::api::get_audience_lists)
+					.service(endpoints::api::post_audience_list)
+					.service(endpoints::api::get_audience_list)
+					.service(endpoints::api::put_audience_list)
+					.service(endpoints::api::delete_audience_list)
+					.service(endpoints::api::get_lists)
+					.service(endpoints::api::post_list)
+					.service(endpoints::api::put_list)
+					.service(endpoints::api::delete_list)
+					.service(endpoints::api::post_list_member)
+					.service(endpoints::api::delete_list_member)
+					.service(endpoints::api::get_notifications),
+			)
 			.service(
 				web::scope("/account")
 					.service(endpoints::account::post_sign_up)
@@ -0,0 +1,193 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use ab_glyph::{point, Font, FontRef, PxScale, ScaleFont};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::mem;
+
+static FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSansCondensed-Bold.ttf");
+
+const MIN_FONT_SIZE: f32 = 12.0;
+const MAX_FONT_SIZE: f32 = 120.0;
+const FILL_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const OUTLINE_COLOR: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+/// In pixels of the template's image.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct TextBox {
+	pub x: u32,
+	pub y: u32,
+	pub width: u32,
+	pub height: u32,
+}
+
+/// White with a black outline, sized to fit the box.
+pub fn render(image: &mut RgbaImage, text_box: &TextBox, text: &str) {
+	let font = FontRef::try_from_slice(FONT).expect("expected the embedded font to be valid");
+	let box_width = text_box.width as f32;
+	let box_height = text_box.height as f32;
+
+	let mut size = box_height.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+	let (font, lines) = loop {
+		let scaled = font.as_scaled(PxScale::from(size));
+		let lines = wrap(&scaled, text, box_width);
+
+		let fits = text_height(&scaled, lines.len()) <= box_height
+			&& lines
+				.iter()
+				.all(|line| line_width(&scaled, line) <= box_width);
+		if fits || size <= MIN_FONT_SIZE {
+			break (scaled, lines);
+		}
+
+		size = (size * 0.9).max(MIN_FONT_SIZE);
+	};
+
+	let outline_width = (size / 16.0).ceil() as i32;
+	let mut y = text_box.y as f32 + (box_height - text_height(&font, lines.len())) / 2.0;
+	for line in lines {
+		let x = text_box.x as f32 + (box_width - line_width(&font, &line)) / 2.0;
+		let baseline = y + font.ascent();
+
+		for dy in -outline_width..=outline_width {
+			for dx in -outline_width..=outline_width {
+				if dx * dx + dy * dy <= outline_width * outline_width {
+					draw_line(
+						image,
+						&font,
+						&line,
+						x + dx as f32,
+						baseline + dy as f32,
+						OUTLINE_COLOR,
+					);
+				}
+			}
+		}
+		draw_line(image, &font, &line, x, baseline, FILL_COLOR);
+
+		y += font.height() + font.line_gap();
+	}
+}
+
+fn wrap<F, SF>(font: &SF, text: &str, max_width: f32) -> Vec<String>
+where
+	F: Font,
+	SF: ScaleFont<F>,
+{
+	let mut lines = Vec::new();
+
+	for paragraph in text.lines() {
+		let mut line = String::new();
+		for word in paragraph.split_whitespace() {
+			let candidate = if line.is_empty() {
+				word.to_string()
+			} else {
+				format!("{} {}", line, word)
+			};
+
+			if !line.is_empty() && line_width(font, &candidate) > max_width {
+				lines.push(mem::take(&mut line));
+				line = word.to_string();
+			} else {
+				line = candidate;
+			}
+		}
+
+		if !line.is_empty() {
+			lines.push(line);
+		}
+	}
+
+	lines
+}
+
+fn line_width<F, SF>(font: &SF, line: &str) -> f32
+where
+	F: Font,
+	SF: ScaleFont<F>,
+{
+	let mut width = 0.0;
+	let mut previous = None;
+
+	for c in line.chars() {
+		let glyph_id = font.glyph_id(c);
+		if let Some(previous) = previous {
+			width += font.kern(previous, glyph_id);
+		}
+
+		width += font.h_advance(glyph_id);
+		previous = Some(glyph_id);
+	}
+
+	width
+}
+
+fn text_height<F, SF>(font: &SF, num_of_lines: usize) -> f32
+where
+	F: Font,
+	SF: ScaleFont<F>,
+{
+	let num_of_gaps = num_of_lines.saturating_sub(1);
+	num_of_lines as f32 * font.height() + num_of_gaps as f32 * font.line_gap()
+}
+
+fn draw_line<F, SF>(
+	image: &mut RgbaImage,
+	font: &SF,
+	line: &str,
+	x: f32,
+	baseline: f32,
+	color: Rgba<u8>,
+) where
+	F: Font,
+	SF: ScaleFont<F>,
+{
+	let mut caret = x;
+	let mut previous = None;
+
+	for c in line.chars() {
+		let glyph_id = font.glyph_id(c);
+		if let Some(previous) = previous {
+			caret += font.kern(previous, glyph_id);
+		}
+
+		let glyph = glyph_id.with_scale_and_position(font.scale(), point(caret, baseline));
+		caret += font.h_advance(glyph_id);
+		previous = Some(glyph_id);
+
+		let outlined = match font.outline_glyph(glyph) {
+			Some(outlined) => outlined,
+			None => continue,
+		};
+
+		let bounds = outlined.px_bounds();
+		outlined.draw(|glyph_x, glyph_y, coverage| {
+			let x = bounds.min.x as i64 + i64::from(glyph_x);
+			let y = bounds.min.y as i64 + i64::from(glyph_y);
+			if x < 0 || y < 0 || x >= i64::from(image.width()) || y >= i64::from(image.height()) {
+				return;
+			}
+
+			let pixel = image.get_pixel_mut(x as u32, y as u32);
+			let alpha = coverage.min(1.0);
+			for i in 0..3 {
+				let blended = f32::from(pixel[i]) * (1.0 - alpha) + f32::from(color[i]) * alpha;
+				pixel[i] = blended.round() as u8;
+			}
+			pixel[3] = pixel[3].max((alpha * 255.0).round() as u8);
+		});
+	}
+}
@@ -0,0 +1,486 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct VideoInfo {
+	pub media_type: &'static str,
+	pub width: u32,
+	pub height: u32,
+	pub duration_ms: Option<u64>,
+}
+
+pub fn probe(bytes: &[u8]) -> Option<VideoInfo> {
+	if bytes.get(4..8) == Some(b"ftyp") {
+		probe_mp4(bytes)
+	} else if bytes.starts_with(&[0x1a, 0x45, 0xdf, 0xa3]) {
+		probe_webm(bytes)
+	} else {
+		None
+	}
+}
+
+struct Mp4Boxes<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> Iterator for Mp4Boxes<'a> {
+	type Item = ([u8; 4], &'a [u8]);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let size = read_uint(self.bytes.get(0..4)?);
+		let kind: [u8; 4] = self.bytes.get(4..8)?.try_into().ok()?;
+
+		let (header_size, size) = match size {
+			0 => (8, self.bytes.len() as u64),
+			1 => (16, read_uint(self.bytes.get(8..16)?)),
+			size => (8, size),
+		};
+		let size = usize::try_from(size).ok()?;
+		if size < header_size {
+			return None;
+		}
+
+		let contents = self.bytes.get(header_size..size)?;
+		self.bytes = &self.bytes[size..];
+
+		Some((kind, contents))
+	}
+}
+
+fn mp4_boxes(bytes: &[u8]) -> Mp4Boxes<'_> {
+	Mp4Boxes { bytes }
+}
+
+fn find_mp4_box<'a>(bytes: &'a [u8], kind: &[u8; 4]) -> Option<&'a [u8]> {
+	mp4_boxes(bytes)
+		.find(|(box_kind, _)| box_kind == kind)
+		.map(|(_, contents)| contents)
+}
+
+fn probe_mp4(bytes: &[u8]) -> Option<VideoInfo> {
+	let moov = find_mp4_box(bytes, b"moov")?;
+
+	// Movie header: version, flags, creation and modification times,
+	// timescale and duration, with 64-bit times and duration in version 1.
+	let mvhd = find_mp4_box(moov, b"mvhd")?;
+	let (timescale, duration) = match mvhd.first()? {
+		0 => (read_uint(mvhd.get(12..16)?), read_uint(mvhd.get(16..20)?)),
+		1 => (read_uint(mvhd.get(20..24)?), read_uint(mvhd.get(24..32)?)),
+		_ => return None,
+	};
+	let duration_ms = if timescale > 0 && duration != u64::MAX && duration != u64::from(u32::MAX) {
+		Some(duration.saturating_mul(1000) / timescale)
+	} else {
+		None
+	};
+
+	let (width, height) = mp4_boxes(moov)
+		.filter(|(kind, _)| kind == b"trak")
+		.filter(|(_, trak)| is_mp4_video_track(trak))
+		.find_map(|(_, trak)| mp4_track_dimensions(trak))?;
+
+	Some(VideoInfo {
+		media_type: "video/mp4",
+		width,
+		height,
+		duration_ms,
+	})
+}
+
+fn is_mp4_video_track(trak: &[u8]) -> bool {
+	// Handler reference: version, flags, predefined value and handler type.
+	find_mp4_box(trak, b"mdia")
+		.and_then(|mdia| find_mp4_box(mdia, b"hdlr"))
+		.and_then(|hdlr| hdlr.get(8..12))
+		== Some(b"vide")
+}
+
+fn mp4_track_dimensions(trak: &[u8]) -> Option<(u32, u32)> {
+	// Track header: width and height are 16.16 fixed-point numbers at its
+	// end, after fields that are longer in version 1.
+	let tkhd = find_mp4_box(trak, b"tkhd")?;
+	let offset = match tkhd.first()? {
+		0 => 76,
+		1 => 88,
+		_ => return None,
+	};
+
+	let width = read_uint(tkhd.get(offset..offset + 4)?) >> 16;
+	let height = read_uint(tkhd.get(offset + 4..offset + 8)?) >> 16;
+	if width == 0 || height == 0 {
+		return None;
+	}
+
+	Some((u32::try_from(width).ok()?, u32::try_from(height).ok()?))
+}
+
+const EBML_DOC_TYPE: u32 = 0x4282;
+const SEGMENT: u32 = 0x18538067;
+const INFO: u32 = 0x1549a966;
+const TIMECODE_SCALE: u32 = 0x2ad7b1;
+const DURATION: u32 = 0x4489;
+const TRACKS: u32 = 0x1654ae6b;
+const TRACK_ENTRY: u32 = 0xae;
+const TRACK_NUMBER: u32 = 0xd7;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const VIDEO: u32 = 0xe0;
+const PIXEL_WIDTH: u32 = 0xb0;
+const PIXEL_HEIGHT: u32 = 0xba;
+const CLUSTER: u32 = 0x1f43b675;
+const SIMPLE_BLOCK: u32 = 0xa3;
+const BLOCK_GROUP: u32 = 0xa0;
+const BLOCK: u32 = 0xa1;
+
+const VIDEO_TRACK_TYPE: u64 = 1;
+
+/// Elements of unknown size are assumed to span until the end of their
+/// parent.
+struct EbmlElements<'a> {
+	bytes: &'a [u8],
+}
+
+impl<'a> Iterator for EbmlElements<'a> {
+	type Item = (u32, &'a [u8]);
+
+	fn next(&mut self) -> Option<Self::Item> {
+		let (id, id_len) = read_vint(self.bytes, false)?;
+		let (size, size_len) = read_vint(self.bytes.get(id_len..)?, true)?;
+		let id = u32::try_from(id?).ok()?;
+
+		let start = id_len + size_len;
+		let end = match size {
+			Some(size) => start.checked_add(usize::try_from(size).ok()?)?,
+			None => self.bytes.len(),
+		};
+
+		let contents = self.bytes.get(start..end.min(self.bytes.len()))?;
+		self.bytes = self.bytes.get(end..).unwrap_or_default();
+
+		Some((id, contents))
+	}
+}
+
+fn ebml_elements(bytes: &[u8]) -> EbmlElements<'_> {
+	EbmlElements { bytes }
+}
+
+fn find_ebml_element(bytes: &[u8], id: u32) -> Option<&[u8]> {
+	ebml_elements(bytes)
+		.take_while(|(element_id, _)| *element_id != CLUSTER)
+		.find(|(element_id, _)| *element_id == id)
+		.map(|(_, contents)| contents)
+}
+
+fn webm_segment(bytes: &[u8]) -> Option<&[u8]> {
+	let mut elements = ebml_elements(bytes);
+
+	let (_, header) = elements.next()?;
+	if find_ebml_element(header, EBML_DOC_TYPE)? != b"webm" {
+		return None;
+	}
+
+	let (id, segment) = elements.next()?;
+	if id != SEGMENT {
+		return None;
+	}
+
+	Some(segment)
+}
+
+fn probe_webm(bytes: &[u8]) -> Option<VideoInfo> {
+	let segment = webm_segment(bytes)?;
+
+	let duration_ms = find_ebml_element(segment, INFO).and_then(|info| {
+		let timecode_scale = find_ebml_element(info, TIMECODE_SCALE)
+			.map(read_uint)
+			.unwrap_or(1000000);
+		let duration = read_float(find_ebml_element(info, DURATION)?)?;
+		if !duration.is_finite() || duration < 0.0 {
+			return None;
+		}
+
+		Some((duration * timecode_scale as f64 / 1000000.0) as u64)
+	});
+
+	let tracks = find_ebml_element(segment, TRACKS)?;
+	let (width, height) = ebml_elements(tracks)
+		.filter(|(id, _)| *id == TRACK_ENTRY)
+		.filter(|(_, entry)| {
+			find_ebml_element(entry, TRACK_TYPE).map(read_uint) == Some(VIDEO_TRACK_TYPE)
+		})
+		.find_map(|(_, entry)| {
+			let video = find_ebml_element(entry, VIDEO)?;
+			let width = read_uint(find_ebml_element(video, PIXEL_WIDTH)?);
+			let height = read_uint(find_ebml_element(video, PIXEL_HEIGHT)?);
+
+			Some((u32::try_from(width).ok()?, u32::try_from(height).ok()?))
+		})?;
+	if width == 0 || height == 0 {
+		return None;
+	}
+
+	Some(VideoInfo {
+		media_type: "video/webm",
+		width,
+		height,
+		duration_ms,
+	})
+}
+
+/// Only VP8, which WebP uses as well, can be decoded without native
+/// libraries.
+pub fn poster_frame(bytes: &[u8]) -> Option<Vec<u8>> {
+	let segment = webm_segment(bytes)?;
+
+	let tracks = find_ebml_element(segment, TRACKS)?;
+	let track_number = ebml_elements(tracks)
+		.filter(|(id, _)| *id == TRACK_ENTRY)
+		.find(|(_, entry)| {
+			find_ebml_element(entry, TRACK_TYPE).map(read_uint) == Some(VIDEO_TRACK_TYPE)
+		})
+		.and_then(|(_, entry)| {
+			if find_ebml_element(entry, CODEC_ID)? != b"V_VP8" {
+				return None;
+			}
+
+			find_ebml_element(entry, TRACK_NUMBER).map(read_uint)
+		})?;
+
+	let frame = ebml_elements(segment)
+		.filter(|(id, _)| *id == CLUSTER)
+		.flat_map(|(_, cluster)| ebml_elements(cluster))
+		.filter_map(|(id, contents)| match id {
+			SIMPLE_BLOCK => Some(contents),
+			BLOCK_GROUP => find_ebml_element(contents, BLOCK),
+			_ => None,
+		})
+		.filter_map(|block| {
+			// Block header: track number, 16-bit timecode and flags. Frames
+			// with lacing are never keyframes of a video track.
+			let (number, number_len) = read_vint(block, true)?;
+			let flags = *block.get(number_len + 2)?;
+			if number? != track_number || flags & 0x06 != 0 {
+				return None;
+			}
+
+			block.get(number_len + 3..)
+		})
+		.next()?;
+
+	// The lowest bit of a VP8 frame tag is 0 for keyframes.
+	if frame.first()? & 1 != 0 {
+		return None;
+	}
+
+	let padding = frame.len() % 2;
+	let chunk_size = u32::try_from(frame.len()).ok()?;
+	let riff_size = chunk_size.checked_add(12 + padding as u32)?;
+
+	let mut webp = Vec::with_capacity(frame.len() + 20 + padding);
+	webp.extend_from_slice(b"RIFF");
+	webp.extend_from_slice(&riff_size.to_le_bytes());
+	webp.extend_from_slice(b"WEBPVP8 ");
+	webp.extend_from_slice(&chunk_size.to_le_bytes());
+	webp.extend_from_slice(frame);
+	webp.resize(webp.len() + padding, 0);
+
+	Some(webp)
+}
+
+/// Sizes with all bits set are unknown.
+fn read_vint(bytes: &[u8], is_size: bool) -> Option<(Option<u64>, usize)> {
+	let first = *bytes.first()?;
+	let len = first.leading_zeros() as usize + 1;
+	if len > 8 || (!is_size && len > 4) {
+		return None;
+	}
+
+	let bytes = bytes.get(..len)?;
+	let mut value = read_uint(bytes);
+	if !is_size {
+		return Some((Some(value), len));
+	}
+
+	value &= (1 << (7 * len)) - 1;
+	if value == (1 << (7 * len)) - 1 {
+		Some((None, len))
+	} else {
+		Some((Some(value), len))
+	}
+}
+
+fn read_uint(bytes: &[u8]) -> u64 {
+	bytes
+		.iter()
+		.take(8)
+		.fold(0, |value, byte| (value << 8) | u64::from(*byte))
+}
+
+fn read_float(bytes: &[u8]) -> Option<f64> {
+	match bytes.len() {
+		4 => Some(f64::from(f32::from_be_bytes(bytes.try_into().ok()?))),
+		8 => Some(f64::from_be_bytes(bytes.try_into().ok()?)),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mp4_box(kind: &[u8; 4], contents: &[u8]) -> Vec<u8> {
+		let mut mp4_box = ((contents.len() + 8) as u32).to_be_bytes().to_vec();
+		mp4_box.extend_from_slice(kind);
+		mp4_box.extend_from_slice(contents);
+		mp4_box
+	}
+
+	fn mp4(duration: u32) -> Vec<u8> {
+		let mut mvhd = vec![0; 100];
+		mvhd[12..16].copy_from_slice(&1000u32.to_be_bytes());
+		mvhd[16..20].copy_from_slice(&duration.to_be_bytes());
+
+		let mut tkhd = vec![0; 84];
+		tkhd[76..80].copy_from_slice(&(640u32 << 16).to_be_bytes());
+		tkhd[80..84].copy_from_slice(&(360u32 << 16).to_be_bytes());
+
+		let mut hdlr = vec![0; 24];
+		hdlr[8..12].copy_from_slice(b"vide");
+
+		let mdia = mp4_box(b"mdia", &mp4_box(b"hdlr", &hdlr));
+		let trak = mp4_box(b"trak", &[mp4_box(b"tkhd", &tkhd), mdia].concat());
+		let moov = mp4_box(b"moov", &[mp4_box(b"mvhd", &mvhd), trak].concat());
+
+		[mp4_box(b"ftyp", b"isom\0\0\0\0"), moov].concat()
+	}
+
+	fn element(id: u32, contents: &[u8]) -> Vec<u8> {
+		let mut element: Vec<u8> = id
+			.to_be_bytes()
+			.into_iter()
+			.skip_while(|byte| *byte == 0)
+			.collect();
+		// Sizes are written on 8 bytes, which is valid for any size.
+		element.push(0x01);
+		element.extend_from_slice(&(contents.len() as u64).to_be_bytes()[1..]);
+		element.extend_from_slice(contents);
+		element
+	}
+
+	fn webm(duration: Option<f64>, codec_id: &[u8], frame: &[u8]) -> Vec<u8> {
+		let header = element(0x1a45dfa3, &element(EBML_DOC_TYPE, b"webm"));
+
+		let mut info = element(TIMECODE_SCALE, &[0x0f, 0x42, 0x40]);
+		if let Some(duration) = duration {
+			info.extend(element(DURATION, &duration.to_be_bytes()));
+		}
+
+		let video = element(
+			VIDEO,
+			&[
+				element(PIXEL_WIDTH, &[0x01, 0x40]),
+				element(PIXEL_HEIGHT, &[0xf0]),
+			]
+			.concat(),
+		);
+		let track_entry = element(
+			TRACK_ENTRY,
+			&[
+				element(TRACK_NUMBER, &[1]),
+				element(TRACK_TYPE, &[1]),
+				element(CODEC_ID, codec_id),
+				video,
+			]
+			.concat(),
+		);
+
+		// Track number 1, timecode 0 and flags of a keyframe.
+		let block = [&[0x81, 0, 0, 0x80], frame].concat();
+		let cluster = element(CLUSTER, &element(SIMPLE_BLOCK, &block));
+
+		let segment = element(
+			SEGMENT,
+			&[element(INFO, &info), element(TRACKS, &track_entry), cluster].concat(),
+		);
+
+		[header, segment].concat()
+	}
+
+	#[test]
+	fn probes_mp4() {
+		assert_eq!(
+			probe(&mp4(2500)),
+			Some(VideoInfo {
+				media_type: "video/mp4",
+				width: 640,
+				height: 360,
+				duration_ms: Some(2500),
+			})
+		);
+	}
+
+	#[test]
+	fn mp4_with_unknown_duration_has_no_duration() {
+		assert_eq!(probe(&mp4(u32::MAX)).unwrap().duration_ms, None);
+	}
+
+	#[test]
+	fn probes_webm() {
+		assert_eq!(
+			probe(&webm(Some(1500.0), b"V_VP8", &[0])),
+			Some(VideoInfo {
+				media_type: "video/webm",
+				width: 320,
+				height: 240,
+				duration_ms: Some(1500),
+			})
+		);
+	}
+
+	#[test]
+	fn webm_without_duration_has_no_duration() {
+		assert_eq!(
+			probe(&webm(None, b"V_VP8", &[0])).unwrap().duration_ms,
+			None
+		);
+	}
+
+	#[test]
+	fn rejects_other_files() {
+		assert_eq!(probe(b"GIF89a"), None);
+		assert_eq!(probe(&mp4(1000)[..40]), None);
+	}
+
+	#[test]
+	fn extracts_vp8_poster_frame() {
+		// The VP8 frame of a lossy WebP image, which is its only chunk.
+		let rgb = [200u8; 16 * 16 * 3];
+		let image = webp::Encoder::from_rgb(&rgb, 16, 16).encode(75.0);
+		assert_eq!(&image[12..16], b"VP8 ");
+		let frame_size = u32::from_le_bytes(image[16..20].try_into().unwrap());
+		let frame = &image[20..20 + frame_size as usize];
+
+		let poster = poster_frame(&webm(Some(1000.0), b"V_VP8", frame)).unwrap();
+		let poster = image::load_from_memory(&poster).unwrap();
+		assert_eq!((poster.width(), poster.height()), (16, 16));
+	}
+
+	#[test]
+	fn does_not_extract_other_codecs() {
+		assert_eq!(poster_frame(&webm(Some(1000.0), b"V_VP9", &[0])), None);
+		assert_eq!(poster_frame(&mp4(1000)), None);
+	}
+}
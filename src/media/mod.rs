@@ -0,0 +1,643 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+pub mod captions;
+pub mod container;
+pub mod phash;
+pub mod processing;
+pub mod proxy;
+pub mod storage;
+pub mod templates;
+
+use crate::activitypub::object_handlers::{ImageLink, ImageMetadata};
+use crate::error::ApiError;
+use crate::state::AppState;
+use actix_web::rt::task;
+use container::VideoInfo;
+use image::io::Reader;
+use image::ImageFormat;
+use processing::EncodedImage;
+use sha2::{Digest, Sha256};
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, Row};
+use std::io::Cursor;
+use tracing::instrument;
+use uuid::Uuid;
+
+/// Maximum width and height of an uploaded image.
+const MAX_DIMENSION: u32 = 10000;
+
+pub const PREVIEW: &str = "preview";
+
+/// From the most preferred format to the least preferred one.
+pub const TRANSCODED_VARIANTS: [(&str, &str); 2] = [("avif", "image/avif"), ("webp", "image/webp")];
+
+#[derive(Clone, Debug)]
+pub struct Media {
+	pub id: Uuid,
+	pub user_id: Uuid,
+	pub media_type: String,
+	pub size: i64,
+	pub width: Option<i32>,
+	pub height: Option<i32>,
+	pub blurhash: Option<String>,
+	pub phash: Option<i64>,
+	pub duration_ms: Option<i64>,
+}
+
+impl Media {
+	fn from_row(row: PgRow) -> Self {
+		Self {
+			id: row.get(0),
+			user_id: row.get(1),
+			media_type: row.get(2),
+			size: row.get(3),
+			width: row.get(4),
+			height: row.get(5),
+			blurhash: row.get(6),
+			phash: row.get(7),
+			duration_ms: row.get(8),
+		}
+	}
+
+	pub fn key(&self) -> String {
+		self.id.to_string()
+	}
+
+	pub fn variant_key(&self, name: &str) -> String {
+		format!("{}_{}", self.id, name)
+	}
+
+	pub fn is_video(&self) -> bool {
+		self.media_type.starts_with("video/")
+	}
+
+	pub fn url(&self, state: &AppState) -> String {
+		state.media_storage.url(&self.key())
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct MediaVariant {
+	pub name: String,
+	pub media_type: String,
+	pub width: i32,
+	pub height: i32,
+}
+
+impl MediaVariant {
+	fn from_row(row: PgRow) -> Self {
+		Self {
+			name: row.get(0),
+			media_type: row.get(1),
+			width: row.get(2),
+			height: row.get(3),
+		}
+	}
+}
+
+/// Returns the MIME type and dimensions of an image.
+pub fn probe(bytes: &[u8]) -> Result<(&'static str, u32, u32), ApiError> {
+	let reader = Reader::new(Cursor::new(bytes)).with_guessed_format()?;
+	let media_type = match reader.format() {
+		Some(ImageFormat::Png) => "image/png",
+		Some(ImageFormat::Jpeg) => "image/jpeg",
+		Some(ImageFormat::Gif) => "image/gif",
+		Some(ImageFormat::WebP) => "image/webp",
+		_ => return Err(ApiError::UnsupportedMediaType),
+	};
+
+	let (width, height) = reader
+		.into_dimensions()
+		.map_err(|_| ApiError::UnsupportedMediaType)?;
+
+	Ok((media_type, width, height))
+}
+
+pub fn probe_media_type(bytes: &[u8]) -> Result<&'static str, ApiError> {
+	match container::probe(bytes) {
+		Some(video) => Ok(video.media_type),
+		None => probe(bytes).map(|(media_type, _, _)| media_type),
+	}
+}
+
+/// Files are deduplicated by their SHA-256 hash. Every user referencing a
+/// file has its full size counted against their quota.
+#[instrument(skip(state, bytes))]
+pub async fn store(state: &AppState, user_id: Uuid, bytes: Vec<u8>) -> Result<Media, ApiError> {
+	let sha256 = Sha256::digest(&bytes).to_vec();
+	if let Some(media) = reuse(state, user_id, &sha256).await? {
+		return Ok(media);
+	}
+
+	// Check dimensions before decoding the whole image.
+	let (_, width, height) = probe(&bytes)?;
+	if width > MAX_DIMENSION || height > MAX_DIMENSION {
+		return Err(ApiError::FileTooLarge);
+	}
+
+	let processed = task::spawn_blocking(move || processing::process(&bytes)).await??;
+	let original = &processed.original;
+
+	let media = Media {
+		id: Uuid::new_v4(),
+		user_id,
+		media_type: original.media_type.to_string(),
+		size: i64::try_from(original.bytes.len())?,
+		width: Some(i32::try_from(original.width)?),
+		height: Some(i32::try_from(original.height)?),
+		blurhash: Some(processed.blurhash.clone()),
+		phash: Some(processed.phash as i64),
+		duration_ms: processed.duration_ms.map(i64::try_from).transpose()?,
+	};
+
+	let mut variants = Vec::new();
+	if let Some(preview) = &processed.preview {
+		variants.push((PREVIEW, preview));
+	}
+	for transcoded in &processed.transcoded {
+		let name = TRANSCODED_VARIANTS
+			.iter()
+			.find(|(_, media_type)| *media_type == transcoded.media_type)
+			.map(|(name, _)| *name)
+			.ok_or(ApiError::InternalServerError)?;
+		variants.push((name, transcoded));
+	}
+
+	save(state, media, &original.bytes, &variants, &sha256).await
+}
+
+/// Videos are stored as they are. The poster frame is taken from VP8 WebM
+/// videos, otherwise the client has to supply it.
+#[instrument(skip(state, bytes, poster))]
+pub async fn store_video(
+	state: &AppState,
+	user_id: Uuid,
+	bytes: Vec<u8>,
+	poster: Option<Vec<u8>>,
+) -> Result<Media, ApiError> {
+	let sha256 = Sha256::digest(&bytes).to_vec();
+	if let Some(media) = reuse(state, user_id, &sha256).await? {
+		return Ok(media);
+	}
+
+	let VideoInfo {
+		media_type,
+		width,
+		height,
+		duration_ms,
+	} = container::probe(&bytes).ok_or(ApiError::UnsupportedMediaType)?;
+	if width > MAX_DIMENSION || height > MAX_DIMENSION {
+		return Err(ApiError::FileTooLarge);
+	}
+	// Without a known duration the limit can't be enforced.
+	let duration_ms = duration_ms.ok_or(ApiError::UnknownVideoDuration)?;
+	if duration_ms > state.max_video_duration_secs.saturating_mul(1000) {
+		return Err(ApiError::VideoTooLong);
+	}
+
+	let poster = poster
+		.or_else(|| container::poster_frame(&bytes))
+		.ok_or(ApiError::PosterRequired)?;
+	let (_, poster_width, poster_height) = probe(&poster)?;
+	if poster_width > MAX_DIMENSION || poster_height > MAX_DIMENSION {
+		return Err(ApiError::FileTooLarge);
+	}
+	let poster = task::spawn_blocking(move || processing::process(&poster)).await??;
+
+	let media = Media {
+		id: Uuid::new_v4(),
+		user_id,
+		media_type: media_type.to_string(),
+		size: i64::try_from(bytes.len())?,
+		width: Some(i32::try_from(width)?),
+		height: Some(i32::try_from(height)?),
+		blurhash: Some(poster.blurhash.clone()),
+		phash: Some(poster.phash as i64),
+		duration_ms: Some(i64::try_from(duration_ms)?),
+	};
+
+	let variants = [(PREVIEW, poster.preview.as_ref().unwrap_or(&poster.original))];
+
+	save(state, media, &bytes, &variants, &sha256).await
+}
+
+async fn reuse(state: &AppState, user_id: Uuid, sha256: &[u8]) -> Result<Option<Media>, ApiError> {
+	let media = match get_by_sha256(state, sha256).await? {
+		Some(media) => media,
+		None => return Ok(None),
+	};
+
+	let size = total_size(state, media.id).await?;
+	add_reference(state, user_id, &media, size).await?;
+
+	Ok(Some(media))
+}
+
+async fn save(
+	state: &AppState,
+	media: Media,
+	bytes: &[u8],
+	variants: &[(&str, &EncodedImage)],
+	sha256: &[u8],
+) -> Result<Media, ApiError> {
+	let user_id = media.user_id;
+
+	let mut size = media.size;
+	for (_, variant) in variants {
+		size += i64::try_from(variant.bytes.len())?;
+	}
+	let mut tx = state.db.begin().await?;
+	check_quota(&mut tx, state, user_id, size).await?;
+
+	state
+		.media_storage
+		.put(&media.key(), bytes, &media.media_type)
+		.await?;
+	for (name, variant) in variants {
+		state
+			.media_storage
+			.put(&media.variant_key(name), &variant.bytes, variant.media_type)
+			.await?;
+	}
+
+	// Someone else could have uploaded the same file in the meantime.
+	let inserted = sqlx::query("INSERT INTO media (id, user_id, media_type, size, width, height, blurhash, phash, duration_ms, sha256) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) ON CONFLICT (sha256) DO NOTHING")
+		.bind(media.id)
+		.bind(media.user_id)
+		.bind(&media.media_type)
+		.bind(media.size)
+		.bind(media.width)
+		.bind(media.height)
+		.bind(&media.blurhash)
+		.bind(media.phash)
+		.bind(media.duration_ms)
+		.bind(sha256)
+		.execute(&mut tx)
+		.await?
+		.rows_affected();
+	if inserted == 0 {
+		tx.rollback().await?;
+		delete_files(state, &media, variants.iter().map(|(name, _)| *name)).await?;
+
+		let media = get_by_sha256(state, sha256)
+			.await?
+			.ok_or(ApiError::InternalServerError)?;
+		add_reference(state, user_id, &media, size).await?;
+		return Ok(media);
+	}
+
+	for (name, variant) in variants {
+		sqlx::query("INSERT INTO media_variants (media_id, name, media_type, size, width, height) VALUES ($1, $2, $3, $4, $5, $6)")
+			.bind(media.id)
+			.bind(name)
+			.bind(variant.media_type)
+			.bind(i64::try_from(variant.bytes.len())?)
+			.bind(i32::try_from(variant.width)?)
+			.bind(i32::try_from(variant.height)?)
+			.execute(&mut tx)
+			.await?;
+	}
+
+	sqlx::query("INSERT INTO media_references (user_id, media_id, size) VALUES ($1, $2, $3)")
+		.bind(user_id)
+		.bind(media.id)
+		.bind(size)
+		.execute(&mut tx)
+		.await?;
+
+	tx.commit().await?;
+
+	Ok(media)
+}
+
+/// The file is deleted once nobody references it and no meme or template
+/// uses it anymore.
+#[instrument(skip(state))]
+pub async fn release(state: &AppState, user_id: Uuid, media_id: Uuid) -> Result<(), ApiError> {
+	let mut tx = state.db.begin().await?;
+
+	// References added in the meantime wait until the file is kept or deleted.
+	sqlx::query("SELECT 1 FROM media WHERE id = $1 FOR UPDATE")
+		.bind(media_id)
+		.execute(&mut tx)
+		.await?;
+
+	let deleted = sqlx::query("DELETE FROM media_references WHERE user_id = $1 AND media_id = $2")
+		.bind(user_id)
+		.bind(media_id)
+		.execute(&mut tx)
+		.await?
+		.rows_affected();
+	if deleted == 0 {
+		return Err(ApiError::ResourceNotFound);
+	}
+
+	// Memes and templates keep showing files after their uploaders release them.
+	let is_used: bool = sqlx::query("SELECT EXISTS (SELECT 1 FROM media_references WHERE media_id = $1) OR EXISTS (SELECT 1 FROM activity_media WHERE media_id = $1) OR EXISTS (SELECT 1 FROM templates WHERE media_id = $1)")
+		.bind(media_id)
+		.fetch_one(&mut tx)
+		.await?
+		.get(0);
+
+	if is_used {
+		tx.commit().await?;
+		return Ok(());
+	}
+
+	let media = get(state, media_id)
+		.await?
+		.ok_or(ApiError::InternalServerError)?;
+	let variants = get_variants(state, media_id).await?;
+
+	sqlx::query("DELETE FROM media WHERE id = $1")
+		.bind(media_id)
+		.execute(&mut tx)
+		.await?;
+
+	tx.commit().await?;
+
+	delete_files(
+		state,
+		&media,
+		variants.iter().map(|variant| variant.name.as_str()),
+	)
+	.await
+}
+
+#[instrument(skip(state))]
+pub async fn set_alt_text(
+	state: &AppState,
+	user_id: Uuid,
+	media_id: Uuid,
+	alt_text: &str,
+) -> Result<(), ApiError> {
+	sqlx::query("UPDATE media_references SET alt_text = $3 WHERE user_id = $1 AND media_id = $2")
+		.bind(user_id)
+		.bind(media_id)
+		.bind(alt_text)
+		.execute(&state.db)
+		.await?;
+
+	Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn get_alt_text(
+	state: &AppState,
+	user_id: Uuid,
+	media_id: Uuid,
+) -> Result<Option<String>, ApiError> {
+	let alt_text: Option<String> =
+		sqlx::query("SELECT alt_text FROM media_references WHERE user_id = $1 AND media_id = $2")
+			.bind(user_id)
+			.bind(media_id)
+			.fetch_optional(&state.db)
+			.await?
+			.and_then(|row| row.get(0));
+
+	Ok(alt_text)
+}
+
+#[instrument(skip(state))]
+pub async fn is_referenced_by(
+	state: &AppState,
+	user_id: Uuid,
+	media_id: Uuid,
+) -> Result<bool, ApiError> {
+	let is_referenced =
+		sqlx::query("SELECT 1 FROM media_references WHERE user_id = $1 AND media_id = $2")
+			.bind(user_id)
+			.bind(media_id)
+			.fetch_optional(&state.db)
+			.await?
+			.is_some();
+
+	Ok(is_referenced)
+}
+
+#[instrument(skip(conn))]
+pub async fn usage(conn: &mut PgConnection, user_id: Uuid) -> Result<i64, ApiError> {
+	let usage: i64 = sqlx::query(
+		"SELECT COALESCE(SUM(size), 0)::bigint FROM media_references WHERE user_id = $1",
+	)
+	.bind(user_id)
+	.fetch_one(conn)
+	.await?
+	.get(0);
+
+	Ok(usage)
+}
+
+/// Locks the user until the transaction ends, so that concurrent uploads are
+/// counted one after another.
+async fn check_quota(
+	conn: &mut PgConnection,
+	state: &AppState,
+	user_id: Uuid,
+	size: i64,
+) -> Result<(), ApiError> {
+	sqlx::query("SELECT 1 FROM users WHERE id = $1 FOR UPDATE")
+		.bind(user_id)
+		.execute(&mut *conn)
+		.await?;
+
+	if usage(conn, user_id).await? + size > i64::try_from(state.media_quota)? {
+		return Err(ApiError::StorageQuotaExceeded);
+	}
+
+	Ok(())
+}
+
+async fn add_reference(
+	state: &AppState,
+	user_id: Uuid,
+	media: &Media,
+	size: i64,
+) -> Result<(), ApiError> {
+	if is_referenced_by(state, user_id, media.id).await? {
+		return Ok(());
+	}
+
+	let mut tx = state.db.begin().await?;
+	check_quota(&mut tx, state, user_id, size).await?;
+
+	sqlx::query("INSERT INTO media_references (user_id, media_id, size) VALUES ($1, $2, $3) ON CONFLICT (user_id, media_id) DO NOTHING")
+		.bind(user_id)
+		.bind(media.id)
+		.bind(size)
+		.execute(&mut tx)
+		.await?;
+
+	tx.commit().await?;
+
+	Ok(())
+}
+
+/// Size of a file with all its variants.
+async fn total_size(state: &AppState, media_id: Uuid) -> Result<i64, ApiError> {
+	let size: i64 = sqlx::query("SELECT size + COALESCE((SELECT SUM(size) FROM media_variants WHERE media_id = media.id), 0)::bigint FROM media WHERE id = $1")
+		.bind(media_id)
+		.fetch_one(&state.db)
+		.await?
+		.get(0);
+
+	Ok(size)
+}
+
+async fn delete_files<'a>(
+	state: &AppState,
+	media: &Media,
+	variant_names: impl Iterator<Item = &'a str>,
+) -> Result<(), ApiError> {
+	state.media_storage.delete(&media.key()).await?;
+	for name in variant_names {
+		state.media_storage.delete(&media.variant_key(name)).await?;
+	}
+
+	Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn get(state: &AppState, id: Uuid) -> Result<Option<Media>, ApiError> {
+	let media = sqlx::query(
+		"SELECT id, user_id, media_type, size, width, height, blurhash, phash, duration_ms FROM media WHERE id = $1",
+	)
+	.bind(id)
+	.map(Media::from_row)
+	.fetch_optional(&state.db)
+	.await?;
+
+	Ok(media)
+}
+
+#[instrument(skip(state))]
+pub async fn get_variant(
+	state: &AppState,
+	media_id: Uuid,
+	name: &str,
+) -> Result<Option<MediaVariant>, ApiError> {
+	let variant = sqlx::query("SELECT name, media_type, width, height FROM media_variants WHERE media_id = $1 AND name = $2")
+		.bind(media_id)
+		.bind(name)
+		.map(MediaVariant::from_row)
+		.fetch_optional(&state.db)
+		.await?;
+
+	Ok(variant)
+}
+
+#[instrument(skip(state))]
+pub async fn get_variants(state: &AppState, media_id: Uuid) -> Result<Vec<MediaVariant>, ApiError> {
+	let variants = sqlx::query(
+		"SELECT name, media_type, width, height FROM media_variants WHERE media_id = $1",
+	)
+	.bind(media_id)
+	.map(MediaVariant::from_row)
+	.fetch_all(&state.db)
+	.await?;
+
+	Ok(variants)
+}
+
+#[instrument(skip(state, sha256))]
+async fn get_by_sha256(state: &AppState, sha256: &[u8]) -> Result<Option<Media>, ApiError> {
+	let media = sqlx::query(
+		"SELECT id, user_id, media_type, size, width, height, blurhash, phash, duration_ms FROM media WHERE sha256 = $1",
+	)
+	.bind(sha256)
+	.map(Media::from_row)
+	.fetch_optional(&state.db)
+	.await?;
+
+	Ok(media)
+}
+
+#[instrument(skip(state))]
+pub async fn find_by_url(state: &AppState, url: &str) -> Result<Option<Media>, ApiError> {
+	let prefix = state.media_storage.url("");
+	let id = match url.strip_prefix(&prefix).map(Uuid::parse_str) {
+		Some(Ok(id)) => id,
+		_ => return Ok(None),
+	};
+
+	get(state, id).await
+}
+
+#[instrument(skip(state))]
+pub async fn image_metadata(state: &AppState, media: &Media) -> Result<ImageMetadata, ApiError> {
+	let variants = get_variants(state, media.id).await?;
+	let link = |variant: &MediaVariant| ImageLink {
+		href: state.media_storage.url(&media.variant_key(&variant.name)),
+		media_type: variant.media_type.clone(),
+		width: Some(variant.width),
+		height: Some(variant.height),
+	};
+
+	let preview = variants
+		.iter()
+		.find(|variant| variant.name == PREVIEW)
+		.map(link);
+	let transcoded = TRANSCODED_VARIANTS
+		.iter()
+		.filter_map(|(name, _)| variants.iter().find(|variant| variant.name == *name))
+		.map(link)
+		.collect();
+
+	Ok(ImageMetadata {
+		link: ImageLink {
+			href: media.url(state),
+			media_type: media.media_type.clone(),
+			width: media.width,
+			height: media.height,
+		},
+		preview,
+		transcoded,
+		blurhash: media.blurhash.clone(),
+		duration_ms: media.duration_ms,
+	})
+}
+
+/// Files a meme shows aren't deleted while it exists.
+#[instrument(skip(conn))]
+pub async fn record_usage(
+	conn: &mut PgConnection,
+	activity_id: Uuid,
+	media_ids: &[Uuid],
+) -> Result<(), ApiError> {
+	sqlx::query("INSERT INTO activity_media (activity_id, media_id) SELECT $1, UNNEST($2::uuid[]) ON CONFLICT DO NOTHING")
+		.bind(activity_id)
+		.bind(media_ids)
+		.execute(conn)
+		.await?;
+
+	Ok(())
+}
+
+#[instrument(skip(conn))]
+pub async fn record_image_hash(
+	conn: &mut PgConnection,
+	activity_id: Uuid,
+	hash: i64,
+) -> Result<(), ApiError> {
+	sqlx::query("INSERT INTO image_hashes (activity_id, phash, phash_bands) VALUES ($1, $2, $3) ON CONFLICT (activity_id) DO NOTHING")
+		.bind(activity_id)
+		.bind(hash)
+		.bind(phash::bands(hash as u64))
+		.execute(conn)
+		.await?;
+
+	Ok(())
+}
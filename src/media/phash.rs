@@ -0,0 +1,58 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use image::imageops::FilterType;
+use image::DynamicImage;
+
+/// Maximum Hamming distance between hashes of similar images.
+pub const MAX_DISTANCE: u32 = 7;
+
+/// Hashes within `MAX_DISTANCE` of each other share at least one of
+/// `MAX_DISTANCE + 1` bands.
+const NUM_OF_BANDS: u32 = 8;
+
+pub fn dhash(image: &DynamicImage) -> u64 {
+	let image = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+
+	let mut hash = 0;
+	for y in 0..8 {
+		for x in 0..8 {
+			let left = image.get_pixel(x, y)[0];
+			let right = image.get_pixel(x + 1, y)[0];
+
+			hash = (hash << 1) | u64::from(left > right);
+		}
+	}
+
+	hash
+}
+
+pub fn distance(a: u64, b: u64) -> u32 {
+	(a ^ b).count_ones()
+}
+
+/// Bands are tagged with their position, so that equal bytes at different
+/// positions don't match.
+pub fn bands(hash: u64) -> Vec<i32> {
+	let band_size = 64 / NUM_OF_BANDS;
+	let mask = (1 << band_size) - 1;
+
+	(0..NUM_OF_BANDS)
+		.map(|i| {
+			let band = (hash >> (i * band_size)) & mask;
+			((i << band_size) | band as u32) as i32
+		})
+		.collect()
+}
@@ -0,0 +1,316 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::phash;
+use crate::error::ApiError;
+use exif::{In, Tag};
+#[cfg(feature = "avif")]
+use image::codecs::avif::AvifEncoder;
+#[cfg(feature = "avif")]
+use image::ImageEncoder;
+use image::{DynamicImage, ImageFormat, ImageOutputFormat};
+use std::io::Cursor;
+use std::iter;
+
+/// Maximum width and height of a preview.
+const PREVIEW_SIZE: u32 = 400;
+/// Width and height of the sample blurhash is computed from.
+const BLURHASH_SAMPLE_SIZE: u32 = 32;
+const JPEG_QUALITY: u8 = 90;
+const PREVIEW_JPEG_QUALITY: u8 = 80;
+const WEBP_QUALITY: f32 = 85.0;
+#[cfg(feature = "avif")]
+const AVIF_QUALITY: u8 = 70;
+#[cfg(feature = "avif")]
+const AVIF_SPEED: u8 = 6;
+
+#[derive(Clone, Debug)]
+pub struct EncodedImage {
+	pub bytes: Vec<u8>,
+	pub media_type: &'static str,
+	pub width: u32,
+	pub height: u32,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProcessedImage {
+	pub original: EncodedImage,
+	/// `None` if the image is small enough already.
+	pub preview: Option<EncodedImage>,
+	/// Only encodings smaller than the original are kept.
+	pub transcoded: Vec<EncodedImage>,
+	pub blurhash: String,
+	pub phash: u64,
+	/// In milliseconds. `None` for still images.
+	pub duration_ms: Option<u64>,
+}
+
+/// Metadata is stripped by re-encoding the image. Animated GIFs are kept as
+/// is, since re-encoding would only keep the first frame, and their preview
+/// doubles as a poster frame.
+pub fn process(bytes: &[u8]) -> Result<ProcessedImage, ApiError> {
+	let format = image::guess_format(bytes).map_err(|_| ApiError::UnsupportedMediaType)?;
+	let image = image::load_from_memory_with_format(bytes, format)
+		.map_err(|_| ApiError::UnsupportedMediaType)?;
+	let image = apply_orientation(image, bytes);
+
+	let original = match format {
+		ImageFormat::Gif => EncodedImage {
+			bytes: bytes.to_vec(),
+			media_type: "image/gif",
+			width: image.width(),
+			height: image.height(),
+		},
+		ImageFormat::Jpeg => encode(&image, false)?,
+		ImageFormat::Png | ImageFormat::WebP => encode(&image, true)?,
+		_ => return Err(ApiError::UnsupportedMediaType),
+	};
+
+	let preview = if image.width() > PREVIEW_SIZE || image.height() > PREVIEW_SIZE {
+		let preview = image.thumbnail(PREVIEW_SIZE, PREVIEW_SIZE);
+		let is_lossless = preview.color().has_alpha();
+
+		Some(encode_with_quality(
+			&preview,
+			is_lossless,
+			PREVIEW_JPEG_QUALITY,
+		)?)
+	} else {
+		None
+	};
+
+	let (transcoded, duration_ms) = if format == ImageFormat::Gif {
+		(Vec::new(), gif_duration(bytes)?)
+	} else {
+		let transcoded = transcode(&image)?
+			.into_iter()
+			.filter(|encoded| encoded.bytes.len() < original.bytes.len())
+			.collect();
+
+		(transcoded, None)
+	};
+
+	Ok(ProcessedImage {
+		original,
+		preview,
+		transcoded,
+		blurhash: blurhash(&image)?,
+		phash: phash::dhash(&image),
+		duration_ms,
+	})
+}
+
+/// Delays are read from the blocks of the GIF, without decoding the frames.
+fn gif_duration(bytes: &[u8]) -> Result<Option<u64>, ApiError> {
+	// Header and logical screen descriptor, followed by the global color table.
+	let flags = *bytes.get(10).ok_or(ApiError::UnsupportedMediaType)?;
+	let mut pos = 13 + gif_color_table_size(flags);
+
+	let mut frame_count = 0;
+	let mut duration_ms = 0;
+	let mut delay_ms = 0;
+	// GIFs that end without a trailer are still shown by browsers.
+	while let Some(block) = bytes.get(pos) {
+		match block {
+			// Extension. Graphic control extensions set the delay of the next
+			// frame in hundredths of a second.
+			0x21 => {
+				if bytes.get(pos + 1) == Some(&0xf9) {
+					let delay = bytes
+						.get(pos + 4..pos + 6)
+						.ok_or(ApiError::UnsupportedMediaType)?;
+					delay_ms = u64::from(u16::from_le_bytes([delay[0], delay[1]])) * 10;
+				}
+				pos = skip_gif_sub_blocks(bytes, pos + 2)?;
+			}
+			// Image descriptor, followed by the local color table, the minimum
+			// LZW code size and the image data.
+			0x2c => {
+				let flags = *bytes.get(pos + 9).ok_or(ApiError::UnsupportedMediaType)?;
+				pos = skip_gif_sub_blocks(bytes, pos + 11 + gif_color_table_size(flags))?;
+				frame_count += 1;
+				duration_ms += delay_ms;
+				delay_ms = 0;
+			}
+			// Trailer.
+			0x3b => break,
+			_ => return Err(ApiError::UnsupportedMediaType),
+		}
+	}
+
+	Ok((frame_count > 1).then_some(duration_ms))
+}
+
+fn gif_color_table_size(flags: u8) -> usize {
+	if flags & 0x80 == 0 {
+		return 0;
+	}
+
+	3 << ((flags & 0x07) + 1)
+}
+
+fn skip_gif_sub_blocks(bytes: &[u8], mut pos: usize) -> Result<usize, ApiError> {
+	loop {
+		let size = usize::from(*bytes.get(pos).ok_or(ApiError::UnsupportedMediaType)?);
+		pos += 1 + size;
+		if size == 0 {
+			return Ok(pos);
+		}
+	}
+}
+
+pub fn encode(image: &DynamicImage, is_lossless: bool) -> Result<EncodedImage, ApiError> {
+	encode_with_quality(image, is_lossless, JPEG_QUALITY)
+}
+
+fn encode_with_quality(
+	image: &DynamicImage,
+	is_lossless: bool,
+	jpeg_quality: u8,
+) -> Result<EncodedImage, ApiError> {
+	let mut bytes = Vec::new();
+
+	let media_type = if is_lossless {
+		image
+			.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+			.map_err(|_| ApiError::InternalServerError)?;
+		"image/png"
+	} else {
+		DynamicImage::ImageRgb8(image.to_rgb8())
+			.write_to(
+				&mut Cursor::new(&mut bytes),
+				ImageOutputFormat::Jpeg(jpeg_quality),
+			)
+			.map_err(|_| ApiError::InternalServerError)?;
+		"image/jpeg"
+	};
+
+	Ok(EncodedImage {
+		bytes,
+		media_type,
+		width: image.width(),
+		height: image.height(),
+	})
+}
+
+fn transcode(image: &DynamicImage) -> Result<Vec<EncodedImage>, ApiError> {
+	// Both encoders only accept 8-bit RGB(A) images.
+	let image = if image.color().has_alpha() {
+		DynamicImage::ImageRgba8(image.to_rgba8())
+	} else {
+		DynamicImage::ImageRgb8(image.to_rgb8())
+	};
+
+	let webp = webp::Encoder::from_image(&image)
+		.map_err(|_| ApiError::InternalServerError)?
+		.encode(WEBP_QUALITY);
+	let webp = EncodedImage {
+		bytes: webp.to_vec(),
+		media_type: "image/webp",
+		width: image.width(),
+		height: image.height(),
+	};
+
+	Ok(iter::once(webp).chain(encode_avif(&image)?).collect())
+}
+
+#[cfg(feature = "avif")]
+fn encode_avif(image: &DynamicImage) -> Result<Option<EncodedImage>, ApiError> {
+	let mut bytes = Vec::new();
+	AvifEncoder::new_with_speed_quality(&mut bytes, AVIF_SPEED, AVIF_QUALITY)
+		.write_image(
+			image.as_bytes(),
+			image.width(),
+			image.height(),
+			image.color(),
+		)
+		.map_err(|_| ApiError::InternalServerError)?;
+
+	Ok(Some(EncodedImage {
+		bytes,
+		media_type: "image/avif",
+		width: image.width(),
+		height: image.height(),
+	}))
+}
+
+#[cfg(not(feature = "avif"))]
+fn encode_avif(_image: &DynamicImage) -> Result<Option<EncodedImage>, ApiError> {
+	Ok(None)
+}
+
+fn apply_orientation(image: DynamicImage, bytes: &[u8]) -> DynamicImage {
+	let orientation = exif::Reader::new()
+		.read_from_container(&mut Cursor::new(bytes))
+		.ok()
+		.and_then(|exif| {
+			exif.get_field(Tag::Orientation, In::PRIMARY)
+				.and_then(|field| field.value.get_uint(0))
+		});
+
+	match orientation {
+		Some(2) => image.fliph(),
+		Some(3) => image.rotate180(),
+		Some(4) => image.flipv(),
+		Some(5) => image.rotate90().fliph(),
+		Some(6) => image.rotate90(),
+		Some(7) => image.rotate270().fliph(),
+		Some(8) => image.rotate270(),
+		_ => image,
+	}
+}
+
+fn blurhash(image: &DynamicImage) -> Result<String, ApiError> {
+	let sample = image
+		.thumbnail(BLURHASH_SAMPLE_SIZE, BLURHASH_SAMPLE_SIZE)
+		.to_rgba8();
+	let (width, height) = sample.dimensions();
+
+	let (components_x, components_y) = if width >= height { (4, 3) } else { (3, 4) };
+
+	blurhash::encode(components_x, components_y, width, height, sample.as_raw())
+		.map_err(|_| ApiError::InternalServerError)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use image::codecs::gif::GifEncoder;
+	use image::{Delay, Frame, RgbaImage};
+
+	fn gif(delays_ms: &[u32]) -> Vec<u8> {
+		let mut bytes = Vec::new();
+		{
+			let mut encoder = GifEncoder::new(&mut bytes);
+			for delay_ms in delays_ms {
+				let frame = Frame::from_parts(
+					RgbaImage::new(4, 4),
+					0,
+					0,
+					Delay::from_numer_denom_ms(*delay_ms, 1),
+				);
+				encoder.encode_frame(frame).unwrap();
+			}
+		}
+
+		bytes
+	}
+
+	#[test]
+	fn sums_gif_frame_delays() {
+		assert_eq!(gif_duration(&gif(&[100, 250, 40])).unwrap(), Some(390));
+		assert_eq!(gif_duration(&gif(&[100])).unwrap(), None);
+	}
+}
@@ -0,0 +1,249 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::{phash, probe_media_type};
+use crate::error::ApiError;
+use crate::http_client;
+use crate::state::AppState;
+use actix_web::rt::task;
+use actix_web::web;
+use awc::http::header;
+use awc::Client;
+use sqlx::postgres::PgRow;
+use sqlx::Row;
+use std::time::Duration;
+use tracing::instrument;
+use url::Url;
+use uuid::Uuid;
+
+thread_local! {
+	// Redirects aren't followed, since they could point to a private address.
+	static CLIENT: Client = http_client::builder()
+		.timeout(Duration::from_secs(30))
+		.disable_redirects()
+		.finish();
+}
+
+#[derive(Clone, Debug)]
+pub struct RemoteMedia {
+	pub id: Uuid,
+	pub url: String,
+	/// `None` if the file isn't cached.
+	pub media_type: Option<String>,
+	pub phash: Option<i64>,
+}
+
+impl RemoteMedia {
+	fn from_row(row: PgRow) -> Self {
+		Self {
+			id: row.get(0),
+			url: row.get(1),
+			media_type: row.get(2),
+			phash: row.get(3),
+		}
+	}
+
+	pub fn key(&self) -> String {
+		key(self.id)
+	}
+}
+
+#[derive(Clone, Debug)]
+pub struct FetchedMedia {
+	pub media_type: String,
+	pub bytes: Vec<u8>,
+	pub phash: Option<i64>,
+}
+
+fn key(id: Uuid) -> String {
+	format!("remote_{}", id)
+}
+
+/// Returns `None` if the URL already points to this server.
+#[instrument(skip(state))]
+pub async fn register(state: &AppState, url: &str) -> Result<Option<Uuid>, ApiError> {
+	if url.starts_with(crate::url::shared_url()) || url.starts_with(&state.media_storage.url("")) {
+		return Ok(None);
+	}
+
+	let url = Url::parse(url)?;
+	http_client::check_url(&url)?;
+
+	let id: Uuid = sqlx::query("INSERT INTO remote_media (id, url) VALUES ($1, $2) ON CONFLICT (url) DO UPDATE SET url = EXCLUDED.url RETURNING id")
+		.bind(Uuid::new_v4())
+		.bind(url.as_str())
+		.fetch_one(&state.db)
+		.await?
+		.get(0);
+
+	Ok(Some(id))
+}
+
+#[instrument(skip(state))]
+pub async fn get(state: &AppState, id: Uuid) -> Result<Option<RemoteMedia>, ApiError> {
+	let remote_media =
+		sqlx::query("SELECT id, url, media_type, phash FROM remote_media WHERE id = $1")
+			.bind(id)
+			.map(RemoteMedia::from_row)
+			.fetch_optional(&state.db)
+			.await?;
+
+	Ok(remote_media)
+}
+
+#[instrument(skip(state))]
+pub async fn touch(state: &AppState, id: Uuid) -> Result<(), ApiError> {
+	sqlx::query(
+		"UPDATE remote_media SET last_accessed_at = NOW() AT TIME ZONE 'utc' WHERE id = $1",
+	)
+	.bind(id)
+	.execute(&state.db)
+	.await?;
+
+	Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn fetch(state: &AppState, remote_media: &RemoteMedia) -> Result<FetchedMedia, ApiError> {
+	let url = Url::parse(&remote_media.url)?;
+	http_client::check_url(&url)?;
+
+	let request = CLIENT.with(|client| {
+		client
+			.get(url.as_str())
+			.insert_header((header::ACCEPT, "image/*, video/mp4, video/webm"))
+			.send()
+	});
+
+	let mut response = request
+		.await
+		.map_err(|_| ApiError::UnexpectedResponseFromFederatedServer)?;
+	if !response.status().is_success() {
+		return Err(ApiError::UnexpectedResponseFromFederatedServer);
+	}
+
+	let bytes = response
+		.body()
+		.limit(state.remote_media_max_size)
+		.await
+		.map_err(|_| ApiError::FileTooLarge)?
+		.to_vec();
+
+	let media_type = probe_media_type(&bytes)?;
+	let (bytes, phash) = task::spawn_blocking(move || {
+		let phash = image::load_from_memory(&bytes)
+			.ok()
+			.map(|image| phash::dhash(&image) as i64);
+
+		(bytes, phash)
+	})
+	.await?;
+
+	state
+		.media_storage
+		.put(&remote_media.key(), &bytes, media_type)
+		.await?;
+
+	sqlx::query("UPDATE remote_media SET media_type = $2, size = $3, phash = COALESCE($4, phash), cached_at = NOW() AT TIME ZONE 'utc', last_accessed_at = NOW() AT TIME ZONE 'utc' WHERE id = $1")
+		.bind(remote_media.id)
+		.bind(media_type)
+		.bind(i64::try_from(bytes.len())?)
+		.bind(phash)
+		.execute(&state.db)
+		.await?;
+
+	Ok(FetchedMedia {
+		media_type: media_type.to_string(),
+		bytes,
+		phash,
+	})
+}
+
+#[instrument(skip(state))]
+pub async fn cache_and_hash(
+	state: web::Data<AppState>,
+	id: Uuid,
+	activity_id: Uuid,
+) -> Result<(), ApiError> {
+	let remote_media = get(&state, id).await?.ok_or(ApiError::ResourceNotFound)?;
+
+	let phash = if remote_media.media_type.is_some() {
+		remote_media.phash
+	} else {
+		fetch(&state, &remote_media).await?.phash
+	};
+
+	if let Some(phash) = phash {
+		super::record_image_hash(&mut *state.db.acquire().await?, activity_id, phash).await?;
+	}
+
+	Ok(())
+}
+
+/// Removes expired files, then least recently used ones until the cache
+/// fits its size limit.
+#[instrument(skip(state))]
+pub async fn prune(state: &AppState) -> Result<(), ApiError> {
+	let expired: Vec<Uuid> = sqlx::query("SELECT id FROM remote_media WHERE cached_at IS NOT NULL AND last_accessed_at < (NOW() AT TIME ZONE 'utc') - make_interval(days => $1)")
+		.bind(state.remote_media_cache_max_age_days)
+		.map(|row: PgRow| row.get(0))
+		.fetch_all(&state.db)
+		.await?;
+
+	for id in expired {
+		evict(state, id).await?;
+	}
+
+	let max_size = i64::try_from(state.remote_media_cache_size)?;
+	let mut size: i64 = sqlx::query(
+		"SELECT COALESCE(SUM(size), 0)::bigint FROM remote_media WHERE cached_at IS NOT NULL",
+	)
+	.fetch_one(&state.db)
+	.await?
+	.get(0);
+
+	while size > max_size {
+		let least_recently_used: Vec<(Uuid, i64)> = sqlx::query("SELECT id, size FROM remote_media WHERE cached_at IS NOT NULL ORDER BY last_accessed_at ASC LIMIT 100")
+			.map(|row: PgRow| (row.get(0), row.get(1)))
+			.fetch_all(&state.db)
+			.await?;
+		if least_recently_used.is_empty() {
+			break;
+		}
+
+		for (id, file_size) in least_recently_used {
+			if size <= max_size {
+				break;
+			}
+
+			evict(state, id).await?;
+			size -= file_size;
+		}
+	}
+
+	Ok(())
+}
+
+#[instrument(skip(state))]
+async fn evict(state: &AppState, id: Uuid) -> Result<(), ApiError> {
+	state.media_storage.delete(&key(id)).await?;
+
+	sqlx::query("UPDATE remote_media SET media_type = NULL, size = NULL, cached_at = NULL, last_accessed_at = NULL WHERE id = $1")
+		.bind(id)
+		.execute(&state.db)
+		.await?;
+
+	Ok(())
+}
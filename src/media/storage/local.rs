@@ -0,0 +1,76 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::Storage;
+use crate::error::ApiError;
+use crate::url;
+use async_trait::async_trait;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use tokio::fs;
+use tracing::instrument;
+
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+	directory: PathBuf,
+}
+
+impl LocalStorage {
+	pub fn new(directory: impl Into<PathBuf>) -> Result<Self, ApiError> {
+		let directory = directory.into();
+		std::fs::create_dir_all(&directory)?;
+
+		Ok(Self { directory })
+	}
+
+	fn path(&self, key: &str) -> PathBuf {
+		self.directory.join(key)
+	}
+}
+
+#[async_trait(?Send)]
+impl Storage for LocalStorage {
+	#[instrument(skip(self, bytes))]
+	async fn put(&self, key: &str, bytes: &[u8], _media_type: &str) -> Result<(), ApiError> {
+		fs::write(self.path(key), bytes).await?;
+		Ok(())
+	}
+
+	#[instrument(skip(self))]
+	async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, ApiError> {
+		match fs::read(self.path(key)).await {
+			Ok(bytes) => Ok(Some(bytes)),
+			Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	#[instrument(skip(self))]
+	async fn delete(&self, key: &str) -> Result<(), ApiError> {
+		match fs::remove_file(self.path(key)).await {
+			Ok(()) => Ok(()),
+			Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+			Err(err) => Err(err.into()),
+		}
+	}
+
+	fn url(&self, key: &str) -> String {
+		url::media(key)
+	}
+
+	fn local_path(&self, key: &str) -> Option<PathBuf> {
+		Some(self.path(key))
+	}
+}
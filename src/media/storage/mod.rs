@@ -0,0 +1,53 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+pub mod local;
+pub mod s3;
+
+pub use local::LocalStorage;
+pub use s3::S3Storage;
+
+use crate::config::MediaStorageConfig;
+use crate::error::ApiError;
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+#[async_trait(?Send)]
+pub trait Storage: Send + Sync {
+	async fn put(&self, key: &str, bytes: &[u8], media_type: &str) -> Result<(), ApiError>;
+	async fn get(&self, key: &str) -> Result<Option<Vec<u8>>, ApiError>;
+	async fn delete(&self, key: &str) -> Result<(), ApiError>;
+
+	fn url(&self, key: &str) -> String;
+
+	/// `None` if the file isn't stored locally.
+	fn local_path(&self, key: &str) -> Option<PathBuf>;
+}
+
+pub fn from_config(config: MediaStorageConfig) -> Result<Box<dyn Storage>, ApiError> {
+	Ok(match config {
+		MediaStorageConfig::Local { directory } => Box::new(LocalStorage::new(directory)?),
+		MediaStorageConfig::S3 {
+			endpoint,
+			region,
+			bucket,
+			access_key,
+			secret_key,
+			public_url,
+		} => Box::new(S3Storage::new(
+			endpoint, region, bucket, access_key, secret_key, public_url,
+		)?),
+	})
+}
@@ -0,0 +1,148 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::captions::{self, TextBox};
+use super::processing;
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::url;
+use actix_web::rt::task;
+use image::DynamicImage;
+use sqlx::postgres::PgRow;
+use sqlx::types::Json;
+use sqlx::Row;
+use tracing::instrument;
+use uuid::Uuid;
+
+pub const MAX_TEXT_BOXES: usize = 10;
+
+#[derive(Clone, Debug)]
+pub struct Template {
+	pub id: Uuid,
+	pub user_id: Uuid,
+	pub name: String,
+	pub media_id: Uuid,
+	pub text_boxes: Vec<TextBox>,
+}
+
+impl Template {
+	fn from_row(row: PgRow) -> Self {
+		let text_boxes: Json<Vec<TextBox>> = row.get(4);
+
+		Self {
+			id: row.get(0),
+			user_id: row.get(1),
+			name: row.get(2),
+			media_id: row.get(3),
+			text_boxes: text_boxes.0,
+		}
+	}
+}
+
+#[instrument(skip(state))]
+pub async fn create(
+	state: &AppState,
+	user_id: Uuid,
+	name: &str,
+	media_id: Uuid,
+	text_boxes: Vec<TextBox>,
+) -> Result<Template, ApiError> {
+	let template = Template {
+		id: Uuid::new_v4(),
+		user_id,
+		name: name.trim().to_string(),
+		media_id,
+		text_boxes,
+	};
+
+	sqlx::query("INSERT INTO templates (id, user_id, name, media_id, text_boxes) VALUES ($1, $2, $3, $4, $5)")
+		.bind(template.id)
+		.bind(template.user_id)
+		.bind(&template.name)
+		.bind(template.media_id)
+		.bind(Json(&template.text_boxes))
+		.execute(&state.db)
+		.await?;
+
+	Ok(template)
+}
+
+#[instrument(skip(state))]
+pub async fn get(state: &AppState, id: Uuid) -> Result<Option<Template>, ApiError> {
+	let template =
+		sqlx::query("SELECT id, user_id, name, media_id, text_boxes FROM templates WHERE id = $1")
+			.bind(id)
+			.map(Template::from_row)
+			.fetch_optional(&state.db)
+			.await?;
+
+	Ok(template)
+}
+
+#[instrument(skip(state))]
+pub async fn list(state: &AppState) -> Result<Vec<Template>, ApiError> {
+	let templates = sqlx::query(
+		"SELECT id, user_id, name, media_id, text_boxes FROM templates ORDER BY name ASC",
+	)
+	.map(Template::from_row)
+	.fetch_all(&state.db)
+	.await?;
+
+	Ok(templates)
+}
+
+#[instrument(skip(state))]
+pub async fn find_by_url(state: &AppState, url: &str) -> Result<Option<Template>, ApiError> {
+	let prefix = format!("{}/templates/", url::shared_url());
+	let id = match url.strip_prefix(&prefix).map(Uuid::parse_str) {
+		Some(Ok(id)) => id,
+		_ => return Ok(None),
+	};
+
+	get(state, id).await
+}
+
+#[instrument(skip(state))]
+pub async fn render(
+	state: &AppState,
+	template: &Template,
+	captions: Vec<String>,
+) -> Result<Vec<u8>, ApiError> {
+	let media = super::get(state, template.media_id)
+		.await?
+		.ok_or(ApiError::InternalServerError)?;
+	let bytes = state
+		.media_storage
+		.get(&media.key())
+		.await?
+		.ok_or(ApiError::InternalServerError)?;
+
+	let text_boxes = template.text_boxes.clone();
+	let is_lossless = media.media_type != "image/jpeg";
+	let encoded = task::spawn_blocking(move || {
+		let mut image = image::load_from_memory(&bytes)
+			.map_err(|_| ApiError::InternalServerError)?
+			.into_rgba8();
+
+		for (text_box, caption) in text_boxes.iter().zip(&captions) {
+			captions::render(&mut image, text_box, caption);
+		}
+
+		processing::encode(&DynamicImage::ImageRgba8(image), is_lossless)
+	})
+	.await??;
+
+	Ok(encoded.bytes)
+}
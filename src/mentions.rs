@@ -0,0 +1,177 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::content;
+use crate::routines::{self, Handle};
+use crate::state::AppState;
+use crate::tags;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::ops::Range;
+use tracing::instrument;
+use url::Url;
+
+/// Others are left as plain text.
+pub const MAX_MENTIONS: usize = 10;
+
+// A mention starts at the beginning of the text or after a character that
+// can't be a part of a word, a handle or a URL, so that e-mail addresses and
+// `/@user` paths aren't mentions. The domain can't end with a dot, so that a
+// mention at the end of a sentence doesn't include the full stop.
+static MENTION_REGEX: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"(?:^|[^\w@/])(@({})(?:@([a-zA-Z0-9-]+(?:\.[a-zA-Z0-9-]+)*(?::[0-9]+)?))?)",
+		routines::USERNAME_PATTERN
+	))
+	.unwrap()
+});
+
+#[derive(Clone, Debug)]
+pub struct Mention {
+	pub handle: Handle,
+	pub actor_url: Url,
+}
+
+impl Mention {
+	/// With the leading `@`, as used in `Mention` tags.
+	pub fn name(&self) -> String {
+		format!("@{}@{}", self.handle.username, self.handle.domain)
+	}
+}
+
+fn find(text: &str, local_domain: &str) -> Vec<(Range<usize>, Handle)> {
+	MENTION_REGEX
+		.captures_iter(text)
+		.filter_map(|captures| {
+			let mut range = captures.get(1).unwrap().range();
+			let handle = match captures.get(3) {
+				Some(domain) => Handle {
+					username: captures[2].to_string(),
+					domain: domain.as_str().to_lowercase(),
+				},
+				// Usernames can contain dots, but a full stop after a mention
+				// isn't a part of it.
+				None => {
+					let username = captures[2].trim_end_matches('.');
+					range.end -= captures[2].len() - username.len();
+					Handle {
+						username: username.to_string(),
+						domain: local_domain.to_lowercase(),
+					}
+				}
+			};
+
+			(!handle.username.is_empty()).then_some((range, handle))
+		})
+		.collect()
+}
+
+/// `@user` is a mention of a user on this instance.
+pub fn parse(text: &str, local_domain: &str) -> Vec<Handle> {
+	let mut handles: Vec<Handle> = Vec::new();
+	for (_, handle) in find(text, local_domain) {
+		if !handles.contains(&handle) {
+			handles.push(handle);
+		}
+	}
+
+	handles
+}
+
+/// Handles that can't be resolved are skipped.
+#[instrument(skip(state))]
+pub async fn resolve(state: &AppState, handles: &[Handle]) -> Vec<Mention> {
+	let mut mentions = Vec::new();
+	for handle in handles.iter().take(MAX_MENTIONS) {
+		if let Ok(actor_url) = routines::resolve_handle(state, handle).await {
+			mentions.push(Mention {
+				handle: handle.clone(),
+				actor_url,
+			});
+		}
+	}
+
+	mentions
+}
+
+pub fn local_usernames(mentions: &[Mention], local_domain: &str) -> Vec<String> {
+	mentions
+		.iter()
+		.filter(|mention| mention.handle.domain == local_domain)
+		.map(|mention| mention.handle.username.clone())
+		.collect()
+}
+
+/// Mentions link to the mentioned actors and hashtags to their timelines.
+pub fn link(text: &str, local_domain: &str, mentions: &[Mention]) -> String {
+	let mut html = String::new();
+	let mut last = 0;
+
+	for (range, handle) in find(text, local_domain) {
+		html.push_str(&tags::link(&text[last..range.start]));
+		match mentions.iter().find(|mention| mention.handle == handle) {
+			Some(mention) => html.push_str(&format!(
+				r#"<span class="h-card"><a href="{}" class="u-url mention">@<span>{}</span></a></span>"#,
+				content::escape(mention.actor_url.as_str()),
+				content::escape(&handle.username),
+			)),
+			None => html.push_str(&content::escape(&text[range.clone()])),
+		}
+		last = range.end;
+	}
+	html.push_str(&tags::link(&text[last..]));
+
+	html
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn handle(username: &str, domain: &str) -> Handle {
+		Handle {
+			username: username.to_string(),
+			domain: domain.to_string(),
+		}
+	}
+
+	#[test]
+	fn parses_mentions() {
+		assert_eq!(
+			parse(
+				"hi @bob, @first.last@Example.com and @alice. mail a@b.c /@x @bob",
+				"local.test"
+			),
+			vec![
+				handle("bob", "local.test"),
+				handle("first.last", "example.com"),
+				handle("alice", "local.test"),
+			]
+		);
+	}
+
+	#[test]
+	fn links_mentions() {
+		let mentions = [Mention {
+			handle: handle("first.last", "example.com"),
+			actor_url: Url::parse("https://example.com/users/first.last").unwrap(),
+		}];
+
+		assert_eq!(
+			link("<@first.last@example.com>.", "local.test", &mentions),
+			"&lt;<span class=\"h-card\"><a href=\"https://example.com/users/first.last\" class=\"u-url mention\">@<span>first.last</span></a></span>&gt;."
+		);
+	}
+}
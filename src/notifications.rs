@@ -0,0 +1,90 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::error::ApiError;
+use crate::state::AppState;
+use chrono::NaiveDateTime;
+use sqlx::postgres::PgRow;
+use sqlx::{PgConnection, Row};
+use tracing::instrument;
+use uuid::Uuid;
+
+pub const MENTION: &str = "mention";
+
+#[derive(Clone, Debug)]
+pub struct Notification {
+	pub kind: String,
+	pub activity_id: Uuid,
+	pub created_at: NaiveDateTime,
+}
+
+impl Notification {
+	fn from_row(row: PgRow) -> Self {
+		Self {
+			kind: row.get(0),
+			activity_id: row.get(1),
+			created_at: row.get(2),
+		}
+	}
+}
+
+/// Users the activity isn't addressed to and its author aren't notified.
+#[instrument(skip(conn))]
+pub async fn record_mentions(
+	conn: &mut PgConnection,
+	activity_id: Uuid,
+	usernames: &[String],
+) -> Result<(), ApiError> {
+	if usernames.is_empty() {
+		return Ok(());
+	}
+
+	const QUERY: &str = "
+		INSERT INTO notifications (user_id, kind, activity_id)
+		SELECT users.id, $3, activities.id
+		FROM activities, users
+		WHERE activities.id = $1
+		AND users.username = ANY($2)
+		AND users.this_instance = TRUE
+		AND users.id <> activities.user_id
+		AND users.id = ANY(activities.to_mentions || activities.cc_mentions)
+		ON CONFLICT DO NOTHING
+	";
+
+	sqlx::query(QUERY)
+		.bind(activity_id)
+		.bind(usernames)
+		.bind(MENTION)
+		.execute(conn)
+		.await?;
+
+	Ok(())
+}
+
+#[instrument(skip(state))]
+pub async fn list(
+	state: &AppState,
+	user_id: Uuid,
+	limit: i64,
+) -> Result<Vec<Notification>, ApiError> {
+	let notifications = sqlx::query("SELECT kind, activity_id, created_at FROM notifications WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2")
+		.bind(user_id)
+		.bind(limit)
+		.map(Notification::from_row)
+		.fetch_all(&state.db)
+		.await?;
+
+	Ok(notifications)
+}
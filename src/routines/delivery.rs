@@ -156,9 +156,7 @@ async fn deliver_activity_inner(
 #[instrument(skip(state))]
 pub async fn retry_deliveries(state: web::Data<AppState>) {
 	loop {
-		let mut queue = state.delivery_retry_queue.lock().unwrap();
-		let first = queue.pop_front();
-		drop(queue);
+		let first = state.delivery_retry_queue.lock().unwrap().pop_front();
 
 		if let Some(failed_delivery) = first {
 			let wait_until = failed_delivery.last_time_tried + Duration::from_secs(3600);
@@ -273,7 +271,7 @@ async fn deliver_activity_innermost(
 			.post(inbox_url.as_str())
 			.insert_header((header::HOST, host_header_val))
 			.insert_header((header::DATE, HttpDate::from(now)))
-			.insert_header(("Digest", (&*digest).clone()))
+			.insert_header(("Digest", (*digest).clone()))
 			.insert_header(("Signature", signature))
 			.insert_header((
 				header::CONTENT_TYPE,
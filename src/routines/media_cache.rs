@@ -0,0 +1,32 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::media;
+use crate::state::AppState;
+use actix_web::rt as actix_rt;
+use actix_web::web;
+use std::time::Duration;
+use tracing::{error, instrument};
+
+#[instrument(skip(state))]
+pub async fn prune_media_cache(state: web::Data<AppState>) {
+	loop {
+		if let Err(err) = media::proxy::prune(&state).await {
+			error!(?err, "Failed to prune the remote media cache");
+		}
+
+		actix_rt::time::sleep(Duration::from_secs(3600)).await;
+	}
+}
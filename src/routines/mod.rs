@@ -14,10 +14,18 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 pub mod delivery;
+pub mod feed_backfill;
+pub mod media_cache;
+pub mod web_finger;
 
 pub use delivery::{deliver_activity, retry_deliveries};
+pub use feed_backfill::backfill_feeds;
+pub use media_cache::prune_media_cache;
+pub use web_finger::{resolve_handle, Handle, USERNAME_PATTERN};
 
+use crate::content;
 use crate::error::ApiError;
+use crate::http_client;
 use crate::state::AppState;
 use activitystreams::actor::properties::ApActorProperties;
 use activitystreams::actor::Person;
@@ -26,14 +34,19 @@ use activitystreams::object::properties::ObjectProperties;
 use activitystreams::BaseBox;
 use awc::http::header;
 use awc::Client;
+use serde_json::Value as JsonValue;
 use sqlx::Row;
 use std::time::Duration;
 use tracing::instrument;
 use url::Url;
 use uuid::Uuid;
 
+pub const KEY_REFRESH_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
 thread_local! {
-	static CLIENT: Client = Client::builder().timeout(Duration::from_secs(10)).finish();
+	static CLIENT: Client = http_client::builder()
+		.timeout(Duration::from_secs(10))
+		.finish();
 }
 
 #[instrument(skip(state))]
@@ -48,23 +61,9 @@ pub async fn fetch_remote_actor(state: &AppState, actor_id: &Url) -> Result<Uuid
 		return Ok(id);
 	}
 
-	if actor_id.scheme() != "https" {
-		return Err(ApiError::OtherBadRequest);
-	}
-
-	let request = CLIENT.with(|client| {
-		client
-			.get(actor_id.as_str())
-			.insert_header((
-				header::ACCEPT,
-				"application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"",
-			))
-			.send()
-	});
-
-	let mut response = request.await?;
-	let body = response.body().await?;
-	let actor: BaseBox = serde_json::from_slice(&body)?;
+	let actor = fetch_actor(actor_id).await?;
+	let public_key = get_public_key_pem(&actor, actor_id);
+	let actor: BaseBox = serde_json::from_value(actor)?;
 
 	match actor.kind() {
 		Some("Person") => {
@@ -88,9 +87,10 @@ pub async fn fetch_remote_actor(state: &AppState, actor_id: &Url) -> Result<Uuid
 			let name = object_props
 				.get_name_xsd_string()
 				.map(|xsd_string| xsd_string.as_str());
+			// Bios are HTML, which is sanitized like content of activities.
 			let summary = object_props
 				.get_summary_xsd_string()
-				.map(|xsd_string| xsd_string.as_str());
+				.map(|xsd_string| content::sanitize(xsd_string.as_str()));
 
 			let ap_actor_props = &actor.extension;
 			let username = ap_actor_props
@@ -99,18 +99,120 @@ pub async fn fetch_remote_actor(state: &AppState, actor_id: &Url) -> Result<Uuid
 				.as_str();
 
 			let id = Uuid::new_v4();
-			sqlx::query("INSERT INTO users (id, username, this_instance, instance_url, name, bio) VALUES ($1, $2, FALSE, $3, $4, $5)")
+			sqlx::query("INSERT INTO users (id, username, this_instance, instance_url, name, bio, public_key, public_key_fetched_at) VALUES ($1, $2, FALSE, $3, $4, $5, $6, NOW() AT TIME ZONE 'utc')")
 				.bind(id)
 				.bind(username)
 				.bind(actor_id.as_str())
 				.bind(name)
 				.bind(summary)
+				.bind(public_key)
 				.execute(&state.db)
 				.await?;
 
 			Ok(id)
 		}
-		Some(_) => todo!("discovering non-person actors"),
+		Some(_) => Err(ApiError::UnsupportedActorType),
 		None => Err(ApiError::UnexpectedResponseFromFederatedServer),
 	}
 }
+
+#[instrument(skip(state))]
+pub async fn fetch_remote_actor_key(
+	state: &AppState,
+	actor_id: &Url,
+) -> Result<(Uuid, String), ApiError> {
+	let user_id = fetch_remote_actor(state, actor_id).await?;
+
+	let public_key: Option<String> = sqlx::query("SELECT public_key FROM users WHERE id = $1")
+		.bind(user_id)
+		.fetch_one(&state.db)
+		.await?
+		.get(0);
+	if let Some(public_key) = public_key {
+		return Ok((user_id, public_key));
+	}
+
+	// Actors discovered before public keys were stored don't have one yet.
+	let public_key = fetch_public_key(actor_id).await?;
+	sqlx::query("UPDATE users SET public_key = $1, public_key_fetched_at = NOW() AT TIME ZONE 'utc' WHERE id = $2")
+		.bind(&public_key)
+		.bind(user_id)
+		.execute(&state.db)
+		.await?;
+
+	Ok((user_id, public_key))
+}
+
+/// Returns `None` if the key was fetched less than [`KEY_REFRESH_INTERVAL`]
+/// ago.
+#[instrument(skip(state))]
+pub async fn refresh_remote_actor_key(
+	state: &AppState,
+	user_id: Uuid,
+	actor_id: &Url,
+) -> Result<Option<String>, ApiError> {
+	let claimed = sqlx::query("UPDATE users SET public_key_fetched_at = NOW() AT TIME ZONE 'utc' WHERE id = $1 AND (public_key_fetched_at IS NULL OR public_key_fetched_at < (NOW() AT TIME ZONE 'utc') - make_interval(secs => $2))")
+		.bind(user_id)
+		.bind(KEY_REFRESH_INTERVAL.as_secs() as f64)
+		.execute(&state.db)
+		.await?
+		.rows_affected();
+	if claimed == 0 {
+		return Ok(None);
+	}
+
+	let public_key = fetch_public_key(actor_id).await?;
+	sqlx::query("UPDATE users SET public_key = $1 WHERE id = $2")
+		.bind(&public_key)
+		.bind(user_id)
+		.execute(&state.db)
+		.await?;
+
+	Ok(Some(public_key))
+}
+
+async fn fetch_public_key(actor_id: &Url) -> Result<String, ApiError> {
+	let actor = fetch_actor(actor_id).await?;
+	get_public_key_pem(&actor, actor_id).ok_or(ApiError::UnexpectedResponseFromFederatedServer)
+}
+
+async fn fetch_actor(actor_id: &Url) -> Result<JsonValue, ApiError> {
+	http_client::check_url(actor_id)?;
+
+	let request = CLIENT.with(|client| {
+		client
+			.get(actor_id.as_str())
+			.insert_header((
+				header::ACCEPT,
+				"application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"",
+			))
+			.send()
+	});
+
+	let mut response = request.await?;
+	let body = response.body().await?;
+	let actor: JsonValue = serde_json::from_slice(&body)?;
+
+	// Otherwise a server could make this instance store any actor under its URL.
+	if actor.get("id").and_then(JsonValue::as_str) != Some(actor_id.as_str()) {
+		return Err(ApiError::UnexpectedResponseFromFederatedServer);
+	}
+
+	Ok(actor)
+}
+
+fn get_public_key_pem(actor: &JsonValue, actor_id: &Url) -> Option<String> {
+	let public_key = actor.get("publicKey")?;
+
+	// A key owned by someone else can't be used to sign as this actor.
+	if let Some(owner) = public_key.get("owner").and_then(JsonValue::as_str) {
+		if owner != actor_id.as_str() {
+			return None;
+		}
+	}
+
+	public_key
+		.get("publicKeyPem")
+		.and_then(JsonValue::as_str)
+		.map(str::to_string)
+}
@@ -0,0 +1,161 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use super::CLIENT;
+use crate::error::ApiError;
+use crate::state::AppState;
+use crate::url as crate_url;
+use awc::http::{header, StatusCode};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Deserialize;
+use sqlx::Row;
+use tracing::instrument;
+use url::Url;
+
+/// Characters usernames in handles consist of, on this and other servers.
+pub const USERNAME_PATTERN: &str = r"[a-zA-Z0-9_.-]+";
+
+static HANDLE_REGEX: Lazy<Regex> = Lazy::new(|| {
+	Regex::new(&format!(
+		r"^(?:acct:|@)?({})@([a-zA-Z0-9.-]+(?::[0-9]+)?)$",
+		USERNAME_PATTERN
+	))
+	.unwrap()
+});
+
+#[derive(Clone, Debug, Deserialize)]
+struct RemoteWebFingerLink {
+	rel: String,
+	#[serde(rename = "type")]
+	kind: Option<String>,
+	href: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+struct RemoteWebFinger {
+	#[serde(default)]
+	links: Vec<RemoteWebFingerLink>,
+}
+
+/// A `user@domain` handle, with domain lowercased.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Handle {
+	pub username: String,
+	pub domain: String,
+}
+
+impl Handle {
+	/// Parses `@user@domain`, `user@domain` and `acct:user@domain`.
+	pub fn parse(handle: &str) -> Option<Self> {
+		let captures = HANDLE_REGEX.captures(handle.trim())?;
+
+		Some(Self {
+			username: captures.get(1).unwrap().as_str().to_string(),
+			domain: captures.get(2).unwrap().as_str().to_lowercase(),
+		})
+	}
+
+	pub fn acct(&self) -> String {
+		format!("acct:{}@{}", self.username, self.domain)
+	}
+}
+
+#[instrument(skip(state))]
+pub async fn resolve_handle(state: &AppState, handle: &Handle) -> Result<Url, ApiError> {
+	if handle.domain == state.domain {
+		let user_exists: bool = sqlx::query(
+			"SELECT EXISTS(SELECT 1 FROM users WHERE username = $1 AND this_instance = TRUE)",
+		)
+		.bind(&handle.username)
+		.fetch_one(&state.db)
+		.await?
+		.get(0);
+		if !user_exists {
+			return Err(ApiError::UserDoesNotExist);
+		}
+
+		return Ok(Url::parse(&crate_url::activitypub_actor(&handle.username))?);
+	}
+
+	let key = format!("{}@{}", handle.username, handle.domain);
+	let cached: Option<String> = sqlx::query("SELECT actor_url FROM web_finger_cache WHERE handle = $1 AND resolved_at > (NOW() AT TIME ZONE 'utc') - INTERVAL '1 day'")
+		.bind(&key)
+		.fetch_optional(&state.db)
+		.await?
+		.map(|row| row.get(0));
+	if let Some(actor_url) = cached {
+		return Ok(Url::parse(&actor_url)?);
+	}
+
+	let actor_url = fetch_actor_url(handle).await?;
+
+	sqlx::query("INSERT INTO web_finger_cache (handle, actor_url, resolved_at) VALUES ($1, $2, NOW() AT TIME ZONE 'utc') ON CONFLICT (handle) DO UPDATE SET actor_url = EXCLUDED.actor_url, resolved_at = EXCLUDED.resolved_at")
+		.bind(&key)
+		.bind(actor_url.as_str())
+		.execute(&state.db)
+		.await?;
+
+	Ok(actor_url)
+}
+
+#[instrument]
+async fn fetch_actor_url(handle: &Handle) -> Result<Url, ApiError> {
+	let web_finger_url = Url::parse_with_params(
+		&format!("https://{}/.well-known/webfinger", handle.domain),
+		&[("resource", handle.acct())],
+	)?;
+
+	let request = CLIENT.with(|client| {
+		client
+			.get(web_finger_url.as_str())
+			.insert_header((header::ACCEPT, "application/jrd+json"))
+			.send()
+	});
+
+	let mut response = request.await?;
+	if response.status() == StatusCode::NOT_FOUND {
+		return Err(ApiError::UserDoesNotExist);
+	}
+	if !response.status().is_success() {
+		return Err(ApiError::UnexpectedResponseFromFederatedServer);
+	}
+
+	let body = response.body().await?;
+	let web_finger: RemoteWebFinger = serde_json::from_slice(&body)
+		.map_err(|_| ApiError::UnexpectedResponseFromFederatedServer)?;
+
+	let href = web_finger
+		.links
+		.into_iter()
+		.find(|link| {
+			link.rel == "self"
+				&& matches!(
+					link.kind.as_deref(),
+					Some("application/activity+json")
+						| Some("application/ld+json; profile=\"https://www.w3.org/ns/activitystreams\"")
+				)
+		})
+		.and_then(|link| link.href)
+		.ok_or(ApiError::UnexpectedResponseFromFederatedServer)?;
+
+	let actor_url =
+		Url::parse(&href).map_err(|_| ApiError::UnexpectedResponseFromFederatedServer)?;
+	if actor_url.scheme() != "https" {
+		return Err(ApiError::UnexpectedResponseFromFederatedServer);
+	}
+
+	Ok(actor_url)
+}
@@ -14,11 +14,27 @@
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::error::ApiError;
-use actix_web::http::header::HttpDate;
+use crate::routines;
+use crate::state::AppState;
+use actix_web::http::header::{self, HttpDate};
 use actix_web::http::Method;
-use rsa::{Hash, PaddingScheme, RsaPrivateKey};
+use actix_web::HttpRequest;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use rsa::pkcs1::DecodeRsaPublicKey;
+use rsa::pkcs8::DecodePublicKey;
+use rsa::{Hash, PaddingScheme, PublicKey, RsaPrivateKey, RsaPublicKey};
 use sha2::{Digest, Sha256};
-use std::time::SystemTime;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use tracing::{instrument, warn};
+use url::Url;
+use uuid::Uuid;
+
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(60 * 60);
+
+static SIGNATURE_PARAM_REGEX: Lazy<Regex> =
+	Lazy::new(|| Regex::new(r#"([a-zA-Z]+)="([^"]*)""#).unwrap());
 
 // Returns the value of `Digest` header.
 #[inline]
@@ -50,5 +66,118 @@ pub fn sign(
 
 	let signed_str = base64::encode(private_key.sign(padding_scheme, &digest)?);
 
-	return Ok(format!("keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date digest\",signature=\"{}\"", key_id, signed_str));
+	Ok(format!("keyId=\"{}\",algorithm=\"rsa-sha256\",headers=\"(request-target) host date digest\",signature=\"{}\"", key_id, signed_str))
+}
+
+/// Returns `None` if the request isn't signed. The `Digest` header isn't
+/// checked, since only requests without a body are supported.
+#[instrument(skip(state, req))]
+pub async fn verify(state: &AppState, req: &HttpRequest) -> Result<Option<Uuid>, ApiError> {
+	let signature_header = match req.headers().get("Signature") {
+		Some(val) => val.to_str().map_err(|_| ApiError::InvalidSignature)?,
+		None => return Ok(None),
+	};
+
+	let params: HashMap<&str, &str> = SIGNATURE_PARAM_REGEX
+		.captures_iter(signature_header)
+		.filter_map(|captures| Some((captures.get(1)?.as_str(), captures.get(2)?.as_str())))
+		.collect();
+
+	let key_id = params.get("keyId").ok_or(ApiError::InvalidSignature)?;
+	let signature = params.get("signature").ok_or(ApiError::InvalidSignature)?;
+	let signature = base64::decode(signature).map_err(|_| ApiError::InvalidSignature)?;
+	let headers: Vec<&str> = params
+		.get("headers")
+		.unwrap_or(&"date")
+		.split_whitespace()
+		.collect();
+	if let Some(algorithm) = params.get("algorithm") {
+		if *algorithm != "rsa-sha256" && *algorithm != "hs2019" {
+			return Err(ApiError::InvalidSignature);
+		}
+	}
+
+	// Without these a signature could be replayed against other resources or
+	// long after it was made.
+	if !headers.contains(&"(request-target)") || !headers.contains(&"date") {
+		return Err(ApiError::InvalidSignature);
+	}
+
+	let date: HttpDate = req
+		.headers()
+		.get(header::DATE)
+		.and_then(|val| val.to_str().ok())
+		.and_then(|val| val.parse().ok())
+		.ok_or(ApiError::InvalidSignature)?;
+	let date = SystemTime::from(date);
+	let now = SystemTime::now();
+	let skew = now
+		.duration_since(date)
+		.or_else(|_| date.duration_since(now))
+		.unwrap_or_default();
+	if skew > MAX_CLOCK_SKEW {
+		return Err(ApiError::InvalidSignature);
+	}
+
+	let mut lines = Vec::with_capacity(headers.len());
+	for name in headers {
+		if name == "(request-target)" {
+			let path = req
+				.uri()
+				.path_and_query()
+				.map(|path| path.as_str())
+				.unwrap_or_else(|| req.uri().path());
+			lines.push(format!(
+				"(request-target): {} {}",
+				req.method().as_str().to_lowercase(),
+				path
+			));
+		} else {
+			let val = req
+				.headers()
+				.get(name)
+				.and_then(|val| val.to_str().ok())
+				.ok_or(ApiError::InvalidSignature)?;
+			lines.push(format!("{}: {}", name, val));
+		}
+	}
+	let str_for_verifying = lines.join("\n");
+
+	let mut actor_id = Url::parse(key_id).map_err(|_| ApiError::InvalidSignature)?;
+	actor_id.set_fragment(None);
+	let (user_id, public_key) = match routines::fetch_remote_actor_key(state, &actor_id).await {
+		Ok(key) => key,
+		// Actors other than people, like instance actors, can't be viewers, so
+		// their requests are treated as anonymous.
+		Err(ApiError::UnsupportedActorType) => return Ok(None),
+		Err(err) => return Err(err),
+	};
+
+	let digest = Sha256::digest(str_for_verifying.as_bytes());
+	if let Err(err) = verify_digest(&public_key, &digest, &signature) {
+		// The actor may have rotated its key since it was stored.
+		let public_key = routines::refresh_remote_actor_key(state, user_id, &actor_id).await?;
+		let result = match public_key {
+			Some(public_key) => verify_digest(&public_key, &digest, &signature),
+			None => Err(err),
+		};
+
+		if let Err(err) = result {
+			warn!(?err, %actor_id, "Request has an invalid signature");
+			return Err(ApiError::InvalidSignature);
+		}
+	}
+
+	Ok(Some(user_id))
+}
+
+fn verify_digest(public_key: &str, digest: &[u8], signature: &[u8]) -> Result<(), ApiError> {
+	let public_key = RsaPublicKey::from_public_key_pem(public_key)
+		.or_else(|_| RsaPublicKey::from_pkcs1_pem(public_key))
+		.map_err(|_| ApiError::UnexpectedResponseFromFederatedServer)?;
+
+	let padding_scheme = PaddingScheme::new_pkcs1v15_sign(Some(Hash::SHA2_256));
+	public_key.verify(padding_scheme, digest, signature)?;
+
+	Ok(())
 }
@@ -13,7 +13,8 @@
 // You should have received a copy of the GNU Affero General Public License
 // along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use crate::config::Config;
+use crate::config::{AddressingPolicy, Config};
+use crate::media::storage::{self, Storage};
 use actix_web::rt::time::Instant;
 use jsonwebtoken::{DecodingKey, EncodingKey};
 use rsa::RsaPrivateKey;
@@ -33,6 +34,17 @@ pub struct AppState {
 	pub token_encoding_key: EncodingKey,
 	pub token_decoding_key: DecodingKey,
 	pub db: Pool<Postgres>,
+	pub media_storage: Box<dyn Storage>,
+	pub max_upload_size: usize,
+	pub max_video_upload_size: usize,
+	pub max_video_duration_secs: u64,
+	pub media_quota: u64,
+	pub require_alt_text: bool,
+	pub timelines_require_sign_in: bool,
+	pub addressing_policy: AddressingPolicy,
+	pub remote_media_max_size: usize,
+	pub remote_media_cache_size: u64,
+	pub remote_media_cache_max_age_days: i32,
 	pub delivery_retry_queue: Mutex<VecDeque<FailedDelivery>>,
 	pub delivery_retry_notify: Notify,
 }
@@ -50,6 +62,8 @@ impl AppState {
 		let token_decoding_key =
 			DecodingKey::from_rsa_pem(&fs::read(config.token_rsa_public_key_pem_filepath)?)?;
 
+		let media_storage = storage::from_config(config.media_storage)?;
+
 		let delivery_retry_queue = Mutex::new(VecDeque::new());
 		let delivery_retry_notify = Notify::new();
 
@@ -59,6 +73,17 @@ impl AppState {
 			token_encoding_key,
 			token_decoding_key,
 			db,
+			media_storage,
+			max_upload_size: config.max_upload_size,
+			max_video_upload_size: config.max_video_upload_size,
+			max_video_duration_secs: config.max_video_duration_secs,
+			media_quota: config.media_quota,
+			require_alt_text: config.require_alt_text,
+			timelines_require_sign_in: config.timelines_require_sign_in,
+			addressing_policy: config.addressing,
+			remote_media_max_size: config.remote_media_max_size,
+			remote_media_cache_size: config.remote_media_cache_size,
+			remote_media_cache_max_age_days: config.remote_media_cache_max_age_days,
 			delivery_retry_queue,
 			delivery_retry_notify,
 		})
@@ -0,0 +1,102 @@
+// ActivityMemes - open-source federated meme-sharing platform.
+// Copyright (C) 2022 asyncth
+//
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU Affero General Public License as published by
+// the Free Software Foundation, version 3 of the License.
+//
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU Affero General Public License for more details.
+//
+// You should have received a copy of the GNU Affero General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::content;
+use crate::error::ApiError;
+use crate::url;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use sqlx::PgConnection;
+use tracing::instrument;
+use uuid::Uuid;
+
+pub const MAX_TAGS: usize = 30;
+/// Without the `#`.
+const MAX_TAG_LENGTH: usize = 100;
+
+// A hashtag starts at the beginning of the text or after a character that
+// can't be a part of a word or a URL, so that `a#b` and `/#anchor` aren't
+// hashtags.
+static HASHTAG_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?:^|[^\w&/#])#(\w+)").unwrap());
+// Hashtags consisting only of digits are usually numbers, as in "#1".
+static TAG_NAME_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"^\w*[^\W\d]\w*$").unwrap());
+
+/// Returns `None` if the tag isn't a valid hashtag.
+pub fn normalize(tag: &str) -> Option<String> {
+	let tag = tag.strip_prefix('#').unwrap_or(tag);
+	if tag.chars().count() > MAX_TAG_LENGTH || !TAG_NAME_REGEX.is_match(tag) {
+		return None;
+	}
+
+	Some(tag.to_lowercase())
+}
+
+pub fn parse<'a>(texts: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+	let mut tags: Vec<String> = Vec::new();
+	for text in texts {
+		for captures in HASHTAG_REGEX.captures_iter(text) {
+			if let Some(tag) = normalize(&captures[1]) {
+				if !tags.contains(&tag) {
+					tags.push(tag);
+				}
+			}
+		}
+	}
+
+	tags
+}
+
+pub fn link(text: &str) -> String {
+	let mut html = String::new();
+	let mut last = 0;
+
+	for captures in HASHTAG_REGEX.captures_iter(text) {
+		let name = captures.get(1).unwrap();
+		let tag = match normalize(name.as_str()) {
+			Some(tag) => tag,
+			None => continue,
+		};
+
+		// The `#` is right before the name.
+		let start = name.start() - 1;
+		html.push_str(&content::escape(&text[last..start]));
+		html.push_str(&format!(
+			r#"<a href="{}" class="mention hashtag">#<span>{}</span></a>"#,
+			content::escape(&url::tag(&tag)),
+			content::escape(name.as_str()),
+		));
+		last = name.end();
+	}
+	html.push_str(&content::escape(&text[last..]));
+
+	html
+}
+
+#[instrument(skip(conn))]
+pub async fn record(
+	conn: &mut PgConnection,
+	activity_id: Uuid,
+	tags: &[String],
+) -> Result<(), ApiError> {
+	for tag in tags.iter().take(MAX_TAGS) {
+		sqlx::query("INSERT INTO tags (activity_id, name) VALUES ($1, $2) ON CONFLICT DO NOTHING")
+			.bind(activity_id)
+			.bind(tag)
+			.execute(&mut *conn)
+			.await?;
+	}
+
+	Ok(())
+}
@@ -73,3 +73,37 @@ pub fn activitypub_activity(id: Uuid) -> String {
 pub fn activitypub_object(activity_id: Uuid) -> String {
 	format!("{}/activities/{}/object", shared_url(), activity_id)
 }
+
+pub fn media(key: &str) -> String {
+	format!("{}/media/{}", shared_url(), key)
+}
+
+pub fn template(id: Uuid) -> String {
+	format!("{}/templates/{}", shared_url(), id)
+}
+
+pub fn media_proxy(id: Uuid) -> String {
+	format!("{}/proxy/{}", shared_url(), id)
+}
+
+pub fn tag(name: &str) -> String {
+	format!("{}/tags/{}", shared_url(), name)
+}
+
+/// Addressing this URI makes an activity visible only to users of this
+/// instance.
+pub fn local_audience() -> String {
+	format!("{}/#Local", shared_url())
+}
+
+pub fn audience_list(id: Uuid) -> String {
+	format!("{}/audiences/{}", shared_url(), id)
+}
+
+pub fn list(username: &str, id: Uuid) -> String {
+	format!("{}/lists/{}", activitypub_actor(username), id)
+}
+
+pub fn timeline(name: &str) -> String {
+	format!("{}/timelines/{}", shared_url(), name)
+}